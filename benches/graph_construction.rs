@@ -0,0 +1,69 @@
+//This benchmark times building the exact and the weighted graph, and one global bfs, on synthetic days of growing size
+//Run it with: cargo bench --bench graph_construction
+//The crate is only a binary, so the modules the graph code needs are compiled in here directly, and what only main or their tests use goes unused
+#![allow(dead_code, unused_imports)]
+
+#[path = "../src/common.rs"]
+mod common;
+#[path = "../src/model.rs"]
+mod model;
+#[path = "../src/search.rs"]
+mod search;
+#[path = "../src/similarity.rs"]
+mod similarity;
+
+use common::{DayType, DaySummary, Lcg, PrecipitationIntensity, TemperatureCategory, Weather};
+use search::{bfs_closest, build_graph, build_weighted_graph, EdgeRule, Exclusion, Scope};
+use similarity::{conditions_of, SimilarityConfig};
+use std::time::Instant;
+
+//This function makes count days with random months, categories and measurements
+fn synthetic_days(count: usize) -> Vec<DaySummary> {
+    let mut random = Lcg(42);
+    (0..count)
+        .map(|i| {
+            let avg_temperature = random.below(400) as f64 / 10.0 - 10.0;
+            DaySummary {
+                date: model::date_of(i as i64),
+                month: random.below(12) as u32 + 1,
+                total_rentals: 5000 + random.below(30000) as i32,
+                weather: [Weather::Sunny, Weather::Rainy, Weather::Snowy, Weather::Foggy][random.below(4)].clone(),
+                temperature_category: match avg_temperature {
+                    t if t > 25.0 => TemperatureCategory::Hot,
+                    t if t < 10.0 => TemperatureCategory::Cold,
+                    _ => TemperatureCategory::Moderate,
+                },
+                precipitation: [PrecipitationIntensity::None, PrecipitationIntensity::Light, PrecipitationIntensity::Heavy][random.below(3)].clone(),
+                avg_temperature,
+                total_rainfall: if random.below(4) == 0 { random.below(300) as f64 / 10.0 } else { 0.0 },
+                total_snowfall: 0.0,
+                avg_humidity: 50.0,
+                day_type: if i % 7 >= 5 { DayType::Weekend } else { DayType::Weekday },
+            }
+        })
+        .collect()
+}
+
+fn main() {
+    let similarity = SimilarityConfig { temperature_c: 10.0, ..SimilarityConfig::default() };
+    for count in [1_000, 10_000, 100_000] {
+        let days = synthetic_days(count);
+
+        let timer = Instant::now();
+        let exact = build_graph(&days);
+        let exact_time = timer.elapsed();
+
+        let timer = Instant::now();
+        let knn = build_weighted_graph(&days, &similarity, EdgeRule::Nearest(5));
+        let knn_time = timer.elapsed();
+
+        let timer = Instant::now();
+        let found = bfs_closest(Scope::Global, &exact, &days, &conditions_of(&days[0]), &similarity, &Exclusion::default()).is_some();
+        let traversal_time = timer.elapsed();
+
+        println!(
+            "{:>7} days: exact {:>9.2?} ({} classes), knn k=5 {:>9.2?} ({} nodes with edges), global bfs {:>9.2?} (found a day: {})",
+            count, exact_time, exact.classes.len(), knn_time, knn.adj_list.len(), traversal_time, found
+        );
+    }
+}
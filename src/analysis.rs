@@ -0,0 +1,369 @@
+//This module measures how strongly each weather measurement moves the demand
+//It correlates every numeric field of the records with the rentals, hourly and per day, and fits a multiple linear regression on all of them
+use crate::common::RawRecord;
+use crate::table::{is_json, write_rows};
+use crate::stats::pearson;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+
+//How a field is combined into one value per day
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Daily {
+    Mean,
+    Sum,
+    //the hour of the day has no daily value
+    Skip,
+}
+
+//A field name, how to read it from a record and how it adds up over a day
+type Field = (&'static str, fn(&RawRecord) -> f64, Daily);
+
+//Every numeric field of RawRecord except the rentals themselves
+const FIELDS: [Field; 9] = [
+    ("hour", |r| r.hour as f64, Daily::Skip),
+    ("temperature", |r| r.temperature, Daily::Mean),
+    ("humidity", |r| r.humidity, Daily::Mean),
+    ("wind_speed", |r| r.wind_speed, Daily::Mean),
+    ("visibility", |r| r.visibility, Daily::Mean),
+    ("dew_point_temp", |r| r.dew_point_temp, Daily::Mean),
+    ("solar_radiation", |r| r.solar_radiation, Daily::Sum),
+    ("rainfall", |r| r.rainfall, Daily::Sum),
+    ("snowfall", |r| r.snowfall, Daily::Sum),
+];
+
+//This struct is the correlation of one field with the rentals; None when the field never changes
+#[derive(Debug, Clone, Serialize)]
+pub struct Correlation {
+    pub field: String,
+    pub pearson: Option<f64>,
+    pub spearman: Option<f64>,
+}
+
+//This struct is one term of a fitted regression; None for a field that never changes, which is left out of the fit
+#[derive(Debug, Clone, Serialize)]
+pub struct Coefficient {
+    pub name: String,
+    pub estimate: Option<f64>,
+    pub std_error: Option<f64>,
+    pub t_value: Option<f64>,
+}
+
+//This struct is a fitted least-squares regression, the intercept being the first coefficient
+#[derive(Debug, Clone, Serialize)]
+pub struct Regression {
+    pub coefficients: Vec<Coefficient>,
+    pub r_squared: f64,
+    pub adjusted_r_squared: f64,
+    pub observations: usize,
+}
+
+//This struct holds the whole analysis for the hourly records and for the daily totals
+#[derive(Debug, Clone, Serialize)]
+pub struct AnalysisReport {
+    pub hourly_correlations: Vec<Correlation>,
+    pub daily_correlations: Vec<Correlation>,
+    pub hourly_regression: Regression,
+    pub daily_regression: Regression,
+}
+
+//One line of the CSV export, with the cells that do not apply to the line left empty
+#[derive(Debug, Serialize)]
+struct ExportRow<'a> {
+    section: &'a str,
+    level: &'a str,
+    name: &'a str,
+    pearson: Option<f64>,
+    spearman: Option<f64>,
+    estimate: Option<f64>,
+    std_error: Option<f64>,
+    t_value: Option<f64>,
+}
+
+//This function replaces each value by its rank, tied values sharing the mean of their ranks
+pub fn ranks(values: &[f64]) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+    let mut ranks = vec![0.0; values.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start;
+        while end + 1 < order.len() && values[order[end + 1]] == values[order[start]] {
+            end += 1;
+        }
+        let shared = (start + end) as f64 / 2.0 + 1.0;
+        for &i in &order[start..=end] {
+            ranks[i] = shared;
+        }
+        start = end + 1;
+    }
+    ranks
+}
+
+//This function computes the Spearman rank correlation, which also picks up relations that are monotonic but not straight lines
+pub fn spearman(xs: &[f64], ys: &[f64]) -> Option<f64> {
+    pearson(&ranks(xs), &ranks(ys))
+}
+
+//This function inverts a square matrix with Gauss-Jordan elimination and partial pivoting
+//Returns None when the matrix is singular, for example when one column is a copy of another
+pub fn invert(matrix: &[Vec<f64>]) -> Option<Vec<Vec<f64>>> {
+    let n = matrix.len();
+    let mut work: Vec<Vec<f64>> = matrix
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let mut row = row.clone();
+            row.extend((0..n).map(|j| if i == j { 1.0 } else { 0.0 }));
+            row
+        })
+        .collect();
+    let scale = matrix.iter().flatten().fold(0.0_f64, |m, v| m.max(v.abs())).max(1.0);
+    for column in 0..n {
+        let pivot = (column..n).max_by(|&a, &b| work[a][column].abs().total_cmp(&work[b][column].abs()))?;
+        if work[pivot][column].abs() < 1e-12 * scale {
+            return None;
+        }
+        work.swap(column, pivot);
+        let divisor = work[column][column];
+        for value in work[column].iter_mut() {
+            *value /= divisor;
+        }
+        let pivot_row = work[column].clone();
+        for (i, row) in work.iter_mut().enumerate() {
+            let factor = row[column];
+            if i != column && factor != 0.0 {
+                for (value, pivot_value) in row.iter_mut().zip(&pivot_row) {
+                    *value -= factor * pivot_value;
+                }
+            }
+        }
+    }
+    Some(work.into_iter().map(|row| row[n..].to_vec()).collect())
+}
+
+//This function fits y = b0 + b1 x1 + ... by ordinary least squares through the normal equations
+//The columns are standardised first so that fields measured in thousands (visibility) and in fractions (rainfall) stay well conditioned
+//A field that never changes (no snow in a summer-only file) carries no information, so it is left out and its coefficient is None
+pub fn linear_regression(names: &[&str], rows: &[Vec<f64>], y: &[f64]) -> Result<Regression, String> {
+    let n = rows.len();
+    let means: Vec<f64> = (0..names.len()).map(|j| rows.iter().map(|r| r[j]).sum::<f64>() / n.max(1) as f64).collect();
+    let spreads: Vec<f64> = (0..names.len())
+        .map(|j| (rows.iter().map(|r| (r[j] - means[j]).powi(2)).sum::<f64>() / n.max(1) as f64).sqrt())
+        .collect();
+    let active: Vec<usize> = (0..names.len()).filter(|&j| spreads[j] > 0.0).collect();
+    let p = active.len() + 1;
+    if n <= p {
+        return Err(format!("{} observations are too few for {} coefficients", n, p));
+    }
+    let design: Vec<Vec<f64>> = rows
+        .iter()
+        .map(|r| std::iter::once(1.0).chain(active.iter().map(|&j| (r[j] - means[j]) / spreads[j])).collect())
+        .collect();
+
+    let mut xtx = vec![vec![0.0; p]; p];
+    let mut xty = vec![0.0; p];
+    for (row, &target) in design.iter().zip(y) {
+        for a in 0..p {
+            xty[a] += row[a] * target;
+            for b in 0..p {
+                xtx[a][b] += row[a] * row[b];
+            }
+        }
+    }
+    let inverse = invert(&xtx).ok_or("the fields are linearly dependent")?;
+    let beta: Vec<f64> = (0..p).map(|a| (0..p).map(|b| inverse[a][b] * xty[b]).sum()).collect();
+
+    let mean_y = y.iter().sum::<f64>() / n as f64;
+    let (mut residual, mut total) = (0.0, 0.0);
+    for (row, &target) in design.iter().zip(y) {
+        let fitted: f64 = row.iter().zip(&beta).map(|(x, b)| x * b).sum();
+        residual += (target - fitted).powi(2);
+        total += (target - mean_y).powi(2);
+    }
+    let sigma2 = residual / (n - p) as f64;
+    let r_squared = if total > 0.0 { 1.0 - residual / total } else { 0.0 };
+
+    //Back to the original units: each slope is divided by its spread, and the intercept absorbs the means
+    let covariance: Vec<Vec<f64>> = inverse.iter().map(|row| row.iter().map(|v| v * sigma2).collect()).collect();
+    let mut coefficients = Vec::with_capacity(names.len() + 1);
+    let slopes: Vec<f64> = active.iter().enumerate().map(|(a, &j)| beta[a + 1] / spreads[j]).collect();
+    let intercept = beta[0] - slopes.iter().zip(&active).map(|(s, &j)| s * means[j]).sum::<f64>();
+    //var(intercept) = c' Cov c with c = (1, -m1/s1, -m2/s2, ...)
+    let c: Vec<f64> = std::iter::once(1.0).chain(active.iter().map(|&j| -means[j] / spreads[j])).collect();
+    let intercept_variance: f64 = (0..p).map(|a| (0..p).map(|b| c[a] * covariance[a][b] * c[b]).sum::<f64>()).sum();
+    //a field left out of the fit has no estimate at all
+    let term = |name: &str, fitted: Option<(f64, f64)>| {
+        let std_error = fitted.map(|(_, variance)| variance.max(0.0).sqrt());
+        Coefficient {
+            name: name.to_string(),
+            estimate: fitted.map(|(estimate, _)| estimate),
+            std_error,
+            t_value: fitted.zip(std_error).map(|((estimate, _), std_error)| if std_error > 0.0 { estimate / std_error } else { 0.0 }),
+        }
+    };
+    coefficients.push(term("intercept", Some((intercept, intercept_variance))));
+    for (j, name) in names.iter().enumerate() {
+        let fitted = active.iter().position(|&k| k == j).map(|a| (slopes[a], covariance[a + 1][a + 1] / spreads[j].powi(2)));
+        coefficients.push(term(name, fitted));
+    }
+
+    Ok(Regression {
+        coefficients,
+        r_squared,
+        adjusted_r_squared: 1.0 - (1.0 - r_squared) * (n - 1) as f64 / (n - p) as f64,
+        observations: n,
+    })
+}
+
+//This function correlates every column with the target
+fn correlations(names: &[&str], rows: &[Vec<f64>], y: &[f64]) -> Vec<Correlation> {
+    names
+        .iter()
+        .enumerate()
+        .map(|(j, name)| {
+            let column: Vec<f64> = rows.iter().map(|r| r[j]).collect();
+            Correlation { field: name.to_string(), pearson: pearson(&column, y), spearman: spearman(&column, y) }
+        })
+        .collect()
+}
+
+//This function runs the whole analysis on the hourly records and on the days they add up to
+pub fn analyze(records: &[RawRecord]) -> Result<AnalysisReport, String> {
+    let hourly_names: Vec<&str> = FIELDS.iter().map(|f| f.0).collect();
+    let hourly_rows: Vec<Vec<f64>> = records.iter().map(|r| FIELDS.iter().map(|f| (f.1)(r)).collect()).collect();
+    let hourly_y: Vec<f64> = records.iter().map(|r| r.rented_bike_count as f64).collect();
+
+    let daily_fields: Vec<_> = FIELDS.iter().filter(|f| f.2 != Daily::Skip).collect();
+    let mut by_date: BTreeMap<&str, Vec<&RawRecord>> = BTreeMap::new();
+    for record in records {
+        by_date.entry(record.date.as_str()).or_default().push(record);
+    }
+    let daily_names: Vec<&str> = daily_fields.iter().map(|f| f.0).collect();
+    let daily_rows: Vec<Vec<f64>> = by_date
+        .values()
+        .map(|hours| {
+            daily_fields
+                .iter()
+                .map(|f| {
+                    let sum: f64 = hours.iter().map(|r| (f.1)(r)).sum();
+                    if f.2 == Daily::Mean { sum / hours.len() as f64 } else { sum }
+                })
+                .collect()
+        })
+        .collect();
+    let daily_y: Vec<f64> = by_date.values().map(|hours| hours.iter().map(|r| r.rented_bike_count as f64).sum()).collect();
+
+    Ok(AnalysisReport {
+        hourly_correlations: correlations(&hourly_names, &hourly_rows, &hourly_y),
+        daily_correlations: correlations(&daily_names, &daily_rows, &daily_y),
+        hourly_regression: linear_regression(&hourly_names, &hourly_rows, &hourly_y).map_err(|e| format!("hourly regression: {}", e))?,
+        daily_regression: linear_regression(&daily_names, &daily_rows, &daily_y).map_err(|e| format!("daily regression: {}", e))?,
+    })
+}
+
+fn print_regression(title: &str, regression: &Regression) {
+    println!("[{}: R² {:.3}, adjusted R² {:.3}, {} observations]", title, regression.r_squared, regression.adjusted_r_squared, regression.observations);
+    println!("  {:<18} {:>12} {:>12} {:>8}", "Term", "Estimate", "Std error", "t");
+    let show = |value: Option<f64>, decimals: usize| value.map_or("n/a".to_string(), |v| format!("{:.*}", decimals, v));
+    for c in &regression.coefficients {
+        println!("  {:<18} {:>12} {:>12} {:>8}", c.name, show(c.estimate, 3), show(c.std_error, 3), show(c.t_value, 2));
+    }
+}
+
+//This function prints the correlations side by side and then both regressions
+pub fn print_analysis(report: &AnalysisReport) {
+    let show = |r: Option<f64>| r.map_or("n/a".to_string(), |r| format!("{:.3}", r));
+    println!("[Correlation with rentals]");
+    println!("  {:<18} {:>15} {:>15} {:>15} {:>15}", "Field", "Hourly Pearson", "Hourly Spearman", "Daily Pearson", "Daily Spearman");
+    for hourly in &report.hourly_correlations {
+        let daily = report.daily_correlations.iter().find(|d| d.field == hourly.field);
+        println!(
+            "  {:<18} {:>15} {:>15} {:>15} {:>15}",
+            hourly.field,
+            show(hourly.pearson),
+            show(hourly.spearman),
+            daily.map_or("-".to_string(), |d| show(d.pearson)),
+            daily.map_or("-".to_string(), |d| show(d.spearman))
+        );
+    }
+    println!();
+    print_regression("Hourly rentals regression", &report.hourly_regression);
+    println!();
+    print_regression("Daily rentals regression", &report.daily_regression);
+}
+
+//This function writes the report as JSON when the path ends in .json, and as one CSV table otherwise
+pub fn write_analysis(path: &str, report: &AnalysisReport) -> Result<(), Box<dyn Error>> {
+    if is_json(path) {
+        fs::write(path, serde_json::to_string_pretty(report)?)?;
+        return Ok(());
+    }
+    let empty = ExportRow { section: "", level: "", name: "", pearson: None, spearman: None, estimate: None, std_error: None, t_value: None };
+    let mut rows = Vec::new();
+    for (level, correlations) in [("hourly", &report.hourly_correlations), ("daily", &report.daily_correlations)] {
+        for c in correlations {
+            rows.push(ExportRow { section: "correlation", level, name: &c.field, pearson: c.pearson, spearman: c.spearman, ..empty });
+        }
+    }
+    for (level, regression) in [("hourly", &report.hourly_regression), ("daily", &report.daily_regression)] {
+        for c in &regression.coefficients {
+            rows.push(ExportRow {
+                section: "coefficient",
+                level,
+                name: &c.name,
+                estimate: c.estimate,
+                std_error: c.std_error,
+                t_value: c.t_value,
+                ..empty
+            });
+        }
+        rows.push(ExportRow { section: "fit", level, name: "r_squared", estimate: Some(regression.r_squared), ..empty });
+        rows.push(ExportRow { section: "fit", level, name: "adjusted_r_squared", estimate: Some(regression.adjusted_r_squared), ..empty });
+    }
+    write_rows(path, &rows)
+}
+
+//The tests check the rank correlation on a monotonic curve and that the regression recovers a known plane, with or without a constant column
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spearman_and_ranks() {
+        assert_eq!(ranks(&[10.0, 30.0, 20.0, 20.0]), [1.0, 4.0, 2.5, 2.5]);
+        let xs = [1.0, 2.0, 3.0, 4.0, 5.0];
+        let ys: Vec<f64> = xs.iter().map(|x: &f64| x.powi(3)).collect();
+        assert!((spearman(&xs, &ys).unwrap() - 1.0).abs() < 1e-12);
+        assert!(pearson(&xs, &ys).unwrap() < 1.0);
+    }
+
+    #[test]
+    fn test_linear_regression() {
+        //y = 3 + 2 a - 0.5 b with a little deterministic noise
+        let rows: Vec<Vec<f64>> = (0..40).map(|i| vec![i as f64, ((i * 7) % 11) as f64 * 100.0]).collect();
+        let y: Vec<f64> = rows.iter().enumerate().map(|(i, r)| 3.0 + 2.0 * r[0] - 0.5 * r[1] + if i % 2 == 0 { 0.1 } else { -0.1 }).collect();
+        let fit = linear_regression(&["a", "b"], &rows, &y).unwrap();
+        assert_eq!(fit.coefficients[0].name, "intercept");
+        assert!((fit.coefficients[0].estimate.unwrap() - 3.0).abs() < 0.1);
+        assert!((fit.coefficients[1].estimate.unwrap() - 2.0).abs() < 0.01);
+        assert!((fit.coefficients[2].estimate.unwrap() + 0.5).abs() < 0.001);
+        assert!(fit.coefficients[1].std_error.is_some_and(|e| e > 0.0 && e < 0.01));
+        assert!(fit.r_squared > 0.999);
+
+        let copied: Vec<Vec<f64>> = rows.iter().map(|r| vec![r[0], r[0] * 2.0]).collect();
+        assert!(linear_regression(&["a", "twice a"], &copied, &y).is_err());
+
+        //a column that never changes is left out of the fit and the other terms come out the same
+        let with_constant: Vec<Vec<f64>> = rows.iter().map(|r| vec![r[0], 0.0, r[1]]).collect();
+        let fit_with_constant = linear_regression(&["a", "snow", "b"], &with_constant, &y).unwrap();
+        assert_eq!(fit_with_constant.coefficients.len(), 4);
+        assert_eq!(fit_with_constant.coefficients[2].name, "snow");
+        assert!(fit_with_constant.coefficients[2].estimate.is_none() && fit_with_constant.coefficients[2].t_value.is_none());
+        for (with, without) in [(0, 0), (1, 1), (3, 2)] {
+            assert!((fit_with_constant.coefficients[with].estimate.unwrap() - fit.coefficients[without].estimate.unwrap()).abs() < 1e-9);
+        }
+        assert!((fit_with_constant.r_squared - fit.r_squared).abs() < 1e-12);
+    }
+}
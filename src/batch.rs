@@ -0,0 +1,157 @@
+//This module answers many queries at once: it reads a CSV or JSON file of conditions, runs the search and prediction
+//for each one and writes the results to a CSV or JSON file
+use crate::common::{Conditions, DaySummary};
+use crate::table::read_rows;
+use crate::search::{bfs_closest, dfs_closest, predict, Adjacency, Exclusion, PredictConfig, Scope};
+use crate::similarity::SimilarityConfig;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+//One row of the input file. Precipitation and the measured values may be left out
+#[derive(Debug, Deserialize)]
+struct ConditionsRow {
+    month: u32,
+    weather: String,
+    temperature: String,
+    #[serde(default)]
+    precipitation: Option<String>,
+    #[serde(default)]
+    temperature_c: Option<f64>,
+    #[serde(default)]
+    rainfall_mm: Option<f64>,
+}
+
+impl ConditionsRow {
+    fn into_conditions(self) -> Result<Conditions, String> {
+        if !(1..=12).contains(&self.month) {
+            return Err(format!("month {} is not between 1 and 12", self.month));
+        }
+        Ok(Conditions {
+            month: self.month,
+            weather: self.weather.parse()?,
+            temperature: self.temperature.parse()?,
+            precipitation: self.precipitation.as_deref().unwrap_or("none").parse()?,
+            temperature_c: self.temperature_c,
+            rainfall_mm: self.rainfall_mm,
+        })
+    }
+}
+
+//This struct is one line of the output: the query, the closest day and the k-nearest estimate
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchResult {
+    pub row: usize,
+    pub month: u32,
+    pub weather: String,
+    pub temperature: String,
+    pub precipitation: String,
+    pub match_date: Option<String>,
+    pub match_rentals: Option<i32>,
+    pub match_score: Option<f64>,
+    pub examined: usize,
+    pub estimate: Option<f64>,
+    pub median: Option<f64>,
+    pub interval_lower: Option<f64>,
+    pub interval_upper: Option<f64>,
+}
+
+//This function reads the queries, numbered from 1 in file order
+//Like load_data, rows that cannot be read are reported and skipped so one typo does not stop the whole batch
+pub fn read_conditions(path: &str) -> Result<Vec<(usize, Conditions)>, Box<dyn Error>> {
+    let rows: Vec<Result<ConditionsRow, String>> = read_rows(path)?;
+
+    let mut queries = Vec::new();
+    for (i, row) in rows.into_iter().enumerate() {
+        match row.and_then(ConditionsRow::into_conditions) {
+            Ok(conditions) => queries.push((i + 1, conditions)),
+            Err(e) => eprintln!("Error Input in row {}: {}", i + 1, e),
+        }
+    }
+    Ok(queries)
+}
+
+//This function runs the closest-day search (bfs or dfs) and the k-nearest prediction for every query
+#[allow(clippy::too_many_arguments)]
+pub fn run_batch<G: Adjacency>(
+    graph: &G,
+    days: &[DaySummary],
+    queries: &[(usize, Conditions)],
+    scope: Scope,
+    similarity: &SimilarityConfig,
+    config: &PredictConfig,
+    exclusion: &Exclusion,
+    depth_first: bool,
+) -> Vec<BatchResult> {
+    queries
+        .iter()
+        .map(|(row, query)| {
+            let closest = if depth_first {
+                dfs_closest(scope, graph, days, query, similarity, exclusion)
+            } else {
+                bfs_closest(scope, graph, days, query, similarity, exclusion)
+            };
+            let prediction = predict(scope, graph, days, query, similarity, config, exclusion);
+            BatchResult {
+                row: *row,
+                month: query.month,
+                weather: query.weather.to_string(),
+                temperature: query.temperature.to_string(),
+                precipitation: query.precipitation.to_string(),
+                match_date: closest.as_ref().map(|c| c.day.date.clone()),
+                match_rentals: closest.as_ref().map(|c| c.day.total_rentals),
+                match_score: closest.as_ref().map(|c| c.score),
+                examined: closest.as_ref().map_or(0, |c| c.examined),
+                estimate: prediction.as_ref().map(|p| p.mean),
+                median: prediction.as_ref().map(|p| p.median),
+                interval_lower: prediction.as_ref().map(|p| p.lower),
+                interval_upper: prediction.as_ref().map(|p| p.upper),
+            }
+        })
+        .collect()
+}
+
+//The test runs a CSV and a JSON batch over two days and checks bad rows are skipped but keep the numbering
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{test_day, PrecipitationIntensity, TemperatureCategory, Weather};
+    use crate::search::build_graph;
+    use crate::table::write_rows;
+    use std::fs;
+
+    #[test]
+    fn test_batch_round_trip() {
+        let days: Vec<DaySummary> = [("01/07/2018", 30000, Weather::Sunny), ("02/07/2018", 9000, Weather::Rainy)]
+            .into_iter()
+            .map(|(date, total_rentals, weather)| DaySummary { weather, temperature_category: TemperatureCategory::Hot, avg_temperature: 28.0, ..test_day(date, total_rentals) })
+            .collect();
+        let graph = build_graph(&days);
+
+        let input = "test_batch_input.csv";
+        fs::write(input, "month,weather,temperature,precipitation\n7,rainy,hot,\n13,sunny,hot,none\n7,Sunny,HOT,none\n").unwrap();
+        let queries = read_conditions(input).unwrap();
+        assert_eq!(queries.iter().map(|(row, _)| *row).collect::<Vec<_>>(), vec![1, 3]);
+
+        let config = PredictConfig { k: 1, ..PredictConfig::default() };
+        let results = run_batch(&graph, &days, &queries, Scope::Global, &SimilarityConfig::default(), &config, &Exclusion::default(), false);
+        assert_eq!(results[0].match_date.as_deref(), Some("02/07/2018"));
+        assert_eq!(results[1].match_rentals, Some(30000));
+        assert_eq!(results[1].estimate, Some(30000.0));
+
+        let output = "test_batch_output.csv";
+        write_rows(output, &results).unwrap();
+        let written = fs::read_to_string(output).unwrap();
+        assert!(written.starts_with("row,month,weather"));
+        assert!(written.contains("3,7,Sunny,Hot,None,01/07/2018,30000"));
+
+        let json_input = "test_batch_input.json";
+        fs::write(json_input, r#"[{"month": 1, "weather": "snowy", "temperature": "cold", "precipitation": "heavy", "temperature_c": -3.5}]"#).unwrap();
+        let queries = read_conditions(json_input).unwrap();
+        assert_eq!(queries[0].1.precipitation, PrecipitationIntensity::Heavy);
+        assert_eq!(queries[0].1.temperature_c, Some(-3.5));
+
+        for path in [input, output, json_input] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,421 @@
+//This module models the hourly rentals with gradient-boosted regression trees, which can pick up interactions such as hot and humid
+//The trees are fitted on the hourly records, and a day is estimated by adding up the estimates of its 24 hours
+use crate::common::{DaySummary, DayType, RawRecord};
+use crate::model::day_type;
+use crate::predictor::{DayQuery, Predictor};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+
+//Names of the hourly features, in the order hour_features() produces them
+pub const HOURLY_FEATURES: [&str; 11] = [
+    "hour", "month", "day_type", "temperature", "humidity", "wind_speed", "visibility", "dew_point_temp", "solar_radiation", "rainfall", "snowfall",
+];
+const WIDTH: usize = HOURLY_FEATURES.len();
+//Positions of the features in the rows, for the code that builds rows from forecasts
+pub const HOUR: usize = 0;
+pub const MONTH: usize = 1;
+pub const DAY_TYPE: usize = 2;
+pub const TEMPERATURE: usize = 3;
+pub const HUMIDITY: usize = 4;
+pub const WIND_SPEED: usize = 5;
+pub const VISIBILITY: usize = 6;
+pub const DEW_POINT: usize = 7;
+pub const SOLAR_RADIATION: usize = 8;
+pub const RAINFALL: usize = 9;
+pub const SNOWFALL: usize = 10;
+//Split points tried per feature; the distinct values are thinned to this many quantiles so a split is found in one pass over the hours
+const MAX_BINS: usize = 32;
+
+//The day type as a number the trees can split on
+pub fn day_type_code(day_type: DayType) -> f64 {
+    match day_type {
+        DayType::Weekday => 0.0,
+        DayType::Weekend => 1.0,
+        DayType::Holiday => 2.0,
+    }
+}
+
+//This function turns one hourly record into its feature vector
+pub fn hour_features(record: &RawRecord) -> Vec<f64> {
+    vec![
+        record.hour as f64,
+        record.month as f64,
+        day_type_code(day_type(&record.date, &record.holiday)),
+        record.temperature,
+        record.humidity,
+        record.wind_speed,
+        record.visibility,
+        record.dew_point_temp,
+        record.solar_radiation,
+        record.rainfall,
+        record.snowfall,
+    ]
+}
+
+//This struct sets how the trees are grown
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BoostingConfig {
+    pub trees: usize,
+    pub learning_rate: f64,
+    pub max_depth: usize,
+    //a split is only made when both sides keep at least this many hours
+    pub min_leaf: usize,
+}
+
+impl Default for BoostingConfig {
+    fn default() -> Self {
+        BoostingConfig { trees: 150, learning_rate: 0.1, max_depth: 5, min_leaf: 20 }
+    }
+}
+
+//One node of a tree, stored in a flat list with the children referring to their positions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Node {
+    Leaf(f64),
+    Split { feature: usize, threshold: f64, left: usize, right: usize },
+}
+
+//This struct is one regression tree; rows with the feature at or below the threshold go left
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegressionTree {
+    pub nodes: Vec<Node>,
+}
+
+impl RegressionTree {
+    //This function checks a tree read from a file: every split needs a known feature and children further down the list,
+    //which also rules out a loop, so predict always reaches a leaf
+    fn is_well_formed(&self) -> bool {
+        !self.nodes.is_empty()
+            && self.nodes.iter().enumerate().all(|(position, node)| match *node {
+                Node::Leaf(_) => true,
+                Node::Split { feature, left, right, .. } => {
+                    feature < WIDTH && [left, right].iter().all(|&child| child > position && child < self.nodes.len())
+                }
+            })
+    }
+
+    pub fn predict(&self, row: &[f64]) -> f64 {
+        let mut position = 0;
+        loop {
+            match self.nodes[position] {
+                Node::Leaf(value) => return value,
+                Node::Split { feature, threshold, left, right } => position = if row[feature] <= threshold { left } else { right },
+            }
+        }
+    }
+}
+
+//This struct is the training data with each feature already sorted into bins
+struct Binned {
+    thresholds: Vec<Vec<f64>>,
+    bins: Vec<Vec<u8>>,
+}
+
+//This function picks the candidate split points of every feature and records the bin of each value
+fn bin_rows(rows: &[Vec<f64>]) -> Binned {
+    let width = rows.first().map_or(0, |r| r.len());
+    let mut thresholds = Vec::with_capacity(width);
+    for j in 0..width {
+        let mut values: Vec<f64> = rows.iter().map(|r| r[j]).collect();
+        values.sort_by(|a, b| a.total_cmp(b));
+        values.dedup();
+        //a split at the last value would leave the right side empty
+        values.pop();
+        let cuts: Vec<f64> = if values.len() <= MAX_BINS {
+            values
+        } else {
+            let mut cuts: Vec<f64> = (1..=MAX_BINS).map(|k| values[k * (values.len() - 1) / MAX_BINS]).collect();
+            cuts.dedup();
+            cuts
+        };
+        thresholds.push(cuts);
+    }
+    let bins = rows
+        .iter()
+        .map(|r| (0..width).map(|j| thresholds[j].partition_point(|&t| t < r[j]) as u8).collect())
+        .collect();
+    Binned { thresholds, bins }
+}
+
+//This function grows one tree on the residuals by repeatedly making the split that lowers the squared error most
+//The lowered error of every split is added to the importance of its feature
+fn grow_tree(binned: &Binned, residuals: &[f64], config: &BoostingConfig, importance: &mut [f64]) -> RegressionTree {
+    let mut nodes = Vec::new();
+    let all: Vec<usize> = (0..residuals.len()).collect();
+    grow_node(binned, residuals, all, 0, config, importance, &mut nodes);
+    RegressionTree { nodes }
+}
+
+fn grow_node(binned: &Binned, residuals: &[f64], members: Vec<usize>, depth: usize, config: &BoostingConfig, importance: &mut [f64], nodes: &mut Vec<Node>) -> usize {
+    let position = nodes.len();
+    let count = members.len() as f64;
+    let total: f64 = members.iter().map(|&i| residuals[i]).sum();
+    nodes.push(Node::Leaf(if members.is_empty() { 0.0 } else { total / count }));
+    if depth >= config.max_depth || members.len() < 2 * config.min_leaf {
+        return position;
+    }
+
+    //For squared error the best split maximises sum_left² / n_left + sum_right² / n_right
+    let parent_score = total * total / count;
+    let mut best: Option<(f64, usize, usize)> = None;
+    for (feature, cuts) in binned.thresholds.iter().enumerate() {
+        if cuts.is_empty() {
+            continue;
+        }
+        let mut sums = vec![0.0; cuts.len() + 1];
+        let mut counts = vec![0usize; cuts.len() + 1];
+        for &i in &members {
+            let bin = binned.bins[i][feature] as usize;
+            sums[bin] += residuals[i];
+            counts[bin] += 1;
+        }
+        let (mut left_sum, mut left_count) = (0.0, 0);
+        for cut in 0..cuts.len() {
+            left_sum += sums[cut];
+            left_count += counts[cut];
+            let right_count = members.len() - left_count;
+            if left_count < config.min_leaf || right_count < config.min_leaf {
+                continue;
+            }
+            let right_sum = total - left_sum;
+            let gain = left_sum * left_sum / left_count as f64 + right_sum * right_sum / right_count as f64 - parent_score;
+            if best.is_none_or(|(best_gain, _, _)| gain > best_gain) {
+                best = Some((gain, feature, cut));
+            }
+        }
+    }
+
+    let Some((gain, feature, cut)) = best.filter(|(gain, _, _)| *gain > 1e-9) else {
+        return position;
+    };
+    importance[feature] += gain;
+    let (left_members, right_members): (Vec<usize>, Vec<usize>) = members.into_iter().partition(|&i| (binned.bins[i][feature] as usize) <= cut);
+    let left = grow_node(binned, residuals, left_members, depth + 1, config, importance, nodes);
+    let right = grow_node(binned, residuals, right_members, depth + 1, config, importance, nodes);
+    nodes[position] = Node::Split { feature, threshold: binned.thresholds[feature][cut], left, right };
+    position
+}
+
+//This struct is a fitted boosted model: the mean plus the shrunken sum of the trees
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoostedModel {
+    pub config: BoostingConfig,
+    pub base: f64,
+    pub trees: Vec<RegressionTree>,
+    //share of the total lowered squared error that each feature's splits account for
+    pub importance: Vec<f64>,
+    //average of every feature at each hour of each month, used to fill in what a DayQuery does not give
+    pub profile: Vec<Vec<Vec<f64>>>,
+}
+
+//This function fits the boosted trees to the rows, each tree correcting what the trees before it got wrong
+pub fn fit_boosting(rows: &[Vec<f64>], targets: &[f64], config: &BoostingConfig) -> Result<BoostedModel, String> {
+    if rows.len() < 2 * config.min_leaf.max(1) {
+        return Err(format!("{} hours are too few to grow trees", rows.len()));
+    }
+    let binned = bin_rows(rows);
+    let base = targets.iter().sum::<f64>() / targets.len() as f64;
+    let mut predictions = vec![base; targets.len()];
+    let mut importance = vec![0.0; rows[0].len()];
+    let mut trees = Vec::with_capacity(config.trees);
+    for _ in 0..config.trees {
+        let residuals: Vec<f64> = targets.iter().zip(&predictions).map(|(t, p)| t - p).collect();
+        let tree = grow_tree(&binned, &residuals, config, &mut importance);
+        for (prediction, row) in predictions.iter_mut().zip(rows) {
+            *prediction += config.learning_rate * tree.predict(row);
+        }
+        trees.push(tree);
+    }
+    let total: f64 = importance.iter().sum();
+    if total > 0.0 {
+        importance.iter_mut().for_each(|v| *v /= total);
+    }
+    Ok(BoostedModel { config: *config, base, trees, importance, profile: hour_profile(rows) })
+}
+
+//This function averages the features per month and hour, falling back to the average over all months for months with no data
+fn hour_profile(rows: &[Vec<f64>]) -> Vec<Vec<Vec<f64>>> {
+    let width = rows.first().map_or(0, |r| r.len());
+    let mut sums = vec![vec![(vec![0.0; width], 0.0); 24]; 13];
+    for row in rows {
+        let hour = (row[HOUR] as usize).min(23);
+        for month in [0, (row[MONTH] as usize).min(12)] {
+            let (sum, count) = &mut sums[month][hour];
+            sum.iter_mut().zip(row).for_each(|(s, v)| *s += v);
+            *count += 1.0;
+        }
+    }
+    //an hour that never occurs at all is left at 0 rather than 0 / 0
+    let average = |(sum, count): &(Vec<f64>, f64)| sum.iter().map(|s| if *count > 0.0 { s / count } else { 0.0 }).collect::<Vec<f64>>();
+    (0..13)
+        .map(|month| (0..24).map(|hour| average(if sums[month][hour].1 > 0.0 { &sums[month][hour] } else { &sums[0][hour] })).collect())
+        .collect()
+}
+
+impl BoostedModel {
+    pub fn predict(&self, row: &[f64]) -> f64 {
+        self.base + self.config.learning_rate * self.trees.iter().map(|tree| tree.predict(row)).sum::<f64>()
+    }
+
+    //This function gives the typical hour of the month, which the hourly forecasts start from
+    pub fn typical_hour(&self, month: u32, hour: usize) -> Vec<f64> {
+        let month = if (1..=12).contains(&month) { month as usize } else { 0 };
+        self.profile[month][hour.min(23)].clone()
+    }
+
+    //This function estimates the 24 hours of a day described only by its daily values
+    //The temperature keeps the usual shape of the day around the given average, and rain and snow are spread evenly
+    pub fn predict_day(&self, query: &DayQuery) -> Vec<f64> {
+        let typical: Vec<Vec<f64>> = (0..24).map(|hour| self.typical_hour(query.month, hour)).collect();
+        let typical_temperature = typical.iter().map(|row| row[TEMPERATURE]).sum::<f64>() / 24.0;
+        let typical_humidity = typical.iter().map(|row| row[HUMIDITY]).sum::<f64>() / 24.0;
+        let shift = query.temperature_c - typical_temperature;
+        typical
+            .into_iter()
+            .map(|mut row| {
+                row[MONTH] = query.month as f64;
+                row[DAY_TYPE] = day_type_code(query.day_type);
+                row[TEMPERATURE] += shift;
+                row[DEW_POINT] += shift;
+                if let Some(humidity) = query.humidity {
+                    row[HUMIDITY] += humidity - typical_humidity;
+                }
+                row[RAINFALL] = query.rainfall_mm / 24.0;
+                row[SNOWFALL] = query.snowfall_cm / 24.0;
+                self.predict(&row).max(0.0)
+            })
+            .collect()
+    }
+}
+
+//This function saves a fitted model as JSON
+pub fn save_model(path: &str, model: &BoostedModel) -> Result<(), Box<dyn Error>> {
+    fs::write(path, serde_json::to_string(model)?)?;
+    Ok(())
+}
+
+//This function loads a model saved by save_model and checks it has the features this version expects
+//and that its trees and hourly profile have the shape predicting relies on, so a damaged file is an error and not a panic
+pub fn load_model(path: &str) -> Result<BoostedModel, Box<dyn Error>> {
+    let model: BoostedModel = serde_json::from_str(&fs::read_to_string(path)?)?;
+    let profile_fits = model.profile.len() == 13 && model.profile.iter().all(|month| month.len() == 24 && month.iter().all(|row| row.len() == WIDTH));
+    if model.importance.len() != WIDTH || !profile_fits {
+        return Err(format!("{} was saved with different features", path).into());
+    }
+    if let Some(position) = model.trees.iter().position(|tree| !tree.is_well_formed()) {
+        return Err(format!("tree {} in {} is damaged", position, path).into());
+    }
+    Ok(model)
+}
+
+//This struct plugs the boosted trees into Predictor: it keeps the hourly records and trains on the hours of the days it is given
+pub struct BoostedPredictor {
+    pub config: BoostingConfig,
+    pub model: Option<BoostedModel>,
+    hours: Vec<(String, Vec<f64>, f64)>,
+}
+
+impl BoostedPredictor {
+    pub fn new(records: &[RawRecord], config: BoostingConfig) -> BoostedPredictor {
+        let hours = records.iter().map(|r| (r.date.clone(), hour_features(r), r.rented_bike_count as f64)).collect();
+        BoostedPredictor { config, model: None, hours }
+    }
+
+    //This function wraps a model that was loaded from a file, so it can predict without the records
+    pub fn with_model(model: BoostedModel) -> BoostedPredictor {
+        BoostedPredictor { config: model.config, model: Some(model), hours: Vec::new() }
+    }
+}
+
+impl Predictor for BoostedPredictor {
+    fn name(&self) -> String {
+        format!("boosted trees ({} trees, depth {})", self.config.trees, self.config.max_depth)
+    }
+
+    fn fit(&mut self, days: &[DaySummary]) -> Result<(), String> {
+        let dates: HashSet<&str> = days.iter().map(|d| d.date.as_str()).collect();
+        let (rows, targets): (Vec<Vec<f64>>, Vec<f64>) =
+            self.hours.iter().filter(|(date, _, _)| dates.contains(date.as_str())).map(|(_, row, target)| (row.clone(), *target)).unzip();
+        self.model = Some(fit_boosting(&rows, &targets, &self.config)?);
+        Ok(())
+    }
+
+    fn predict(&self, query: &DayQuery) -> Option<f64> {
+        Some(self.model.as_ref()?.predict_day(query).iter().sum())
+    }
+}
+
+//The tests check that a tree finds a clear split, that boosting learns an interaction a straight line cannot, and that a damaged model file is refused
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tree_split() {
+        let rows: Vec<Vec<f64>> = (0..100).map(|i| vec![i as f64, (i % 7) as f64]).collect();
+        let targets: Vec<f64> = rows.iter().map(|r| if r[0] < 40.0 { 10.0 } else { 50.0 }).collect();
+        let config = BoostingConfig { trees: 1, learning_rate: 1.0, max_depth: 1, min_leaf: 5 };
+        let mut importance = vec![0.0; 2];
+        let tree = grow_tree(&bin_rows(&rows), &targets, &config, &mut importance);
+        assert_eq!(tree.predict(&[10.0, 0.0]), 10.0);
+        assert_eq!(tree.predict(&[90.0, 0.0]), 50.0);
+        assert!(importance[0] > 0.0 && importance[1] == 0.0);
+    }
+
+    #[test]
+    fn test_boosting_interaction() {
+        //rentals only drop when it is both hot and humid
+        let rows: Vec<Vec<f64>> = (0..400).map(|i| vec![(i % 20) as f64 + 15.0, ((i / 20) % 20) as f64 * 5.0]).collect();
+        let targets: Vec<f64> = rows.iter().map(|r| if r[0] > 28.0 && r[1] > 60.0 { 200.0 } else { 1000.0 }).collect();
+        let model = fit_boosting(&rows, &targets, &BoostingConfig { trees: 50, learning_rate: 0.3, max_depth: 3, min_leaf: 5 }).unwrap();
+        assert!((model.predict(&[32.0, 90.0]) - 200.0).abs() < 50.0);
+        assert!((model.predict(&[32.0, 20.0]) - 1000.0).abs() < 50.0);
+        assert!((model.predict(&[18.0, 90.0]) - 1000.0).abs() < 50.0);
+        assert!((model.importance.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+
+        let saved = serde_json::to_string(&model).unwrap();
+        let loaded: BoostedModel = serde_json::from_str(&saved).unwrap();
+        assert!((loaded.predict(&[32.0, 90.0]) - model.predict(&[32.0, 90.0])).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_load_model_rejects_damage() {
+        let path = "test_boosting_model.json";
+        let rows: Vec<Vec<f64>> = (0..200).map(|i| (0..WIDTH).map(|j| ((i * (j + 1)) % 24) as f64).collect()).collect();
+        let targets: Vec<f64> = rows.iter().map(|r| r[HOUR] * 10.0).collect();
+        let model = fit_boosting(&rows, &targets, &BoostingConfig { trees: 3, learning_rate: 0.5, max_depth: 2, min_leaf: 5 }).unwrap();
+        save_model(path, &model).unwrap();
+        assert!(load_model(path).is_ok());
+
+        //the root of the first tree is a split, which is damaged in each of the ways predict could not follow
+        let Node::Split { feature, threshold, left, right } = model.trees[0].nodes[0] else { panic!("the first tree did not split") };
+        let size = model.trees[0].nodes.len();
+        let mut models = Vec::new();
+        for node in [
+            Node::Split { feature: WIDTH, threshold, left, right },
+            Node::Split { feature, threshold, left: 0, right },
+            Node::Split { feature, threshold, left, right: size },
+        ] {
+            let mut broken = model.clone();
+            broken.trees[0].nodes[0] = node;
+            models.push(broken);
+        }
+        let mut empty_tree = model.clone();
+        empty_tree.trees[1].nodes.clear();
+        models.push(empty_tree);
+        let mut short_profile = model.clone();
+        short_profile.profile[3].pop();
+        models.push(short_profile);
+        let mut narrow_profile = model.clone();
+        narrow_profile.profile[5][7].pop();
+        models.push(narrow_profile);
+
+        for broken in models {
+            save_model(path, &broken).unwrap();
+            assert!(load_model(path).is_err());
+        }
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,531 @@
+//This module reads the command line: a subcommand such as query or stats followed by its flags
+//Every mistake is reported as an error message instead of being ignored, so scripts can rely on the exit code
+use crate::boosting::BoostingConfig;
+use crate::common::{Conditions, DayType};
+use crate::evaluation::Scheme;
+use crate::export::ExportFormat;
+use crate::forecast::{ForecastMethod, ALL_METHODS};
+use crate::graph_analysis::Centrality;
+use crate::predictor::DayQuery;
+use crate::search::{Anchor, EdgeRule, Exclusion, PredictConfig, Scope, Weighting};
+use crate::similarity::{load_config, SimilarityConfig};
+use crate::stats::Dimension;
+
+pub const USAGE: &str = "\
+Usage: finalproject <command> [flags]
+
+Commands:
+  stats                          demand statistics by season, weather, temperature, precipitation,
+                                 day type and wind speed, with significance tests between the groups
+  analyze                        correlations and linear regression of every measurement vs rentals
+  query                          find the closest historical day with bfs and dfs
+  predict                        estimate rentals from the k nearest days, or with --model linear|ridge
+  predict-hours                  estimate each hour of a day from an hourly --forecast file
+  forecast                       forecast the --days after the data ends from the history alone
+  evaluate                       backtest every estimator and print a leaderboard of their errors
+  export                         write the day graph to a DOT, GraphML or JSON file
+  graph analyze                  components, degrees and communities of the graph
+  graph path                     chain of similar days between --from and --to
+  graph representatives          most central day of each component or community
+  fit-weights                    learn similarity weights and save them to --output
+  interactive                    ask for conditions on the terminal
+  help                           show this message
+
+Data:
+  --data FILE                    bike CSV to load (default: SeoulBikeData 4.csv)
+  --index FILE                   cache the hourly records, summaries and graph in FILE between runs
+
+Conditions (query, predict):
+  --month 1-12 --weather sunny|rainy|snowy|foggy --temp hot|moderate|cold
+  --precip none|light|heavy      (default none)
+  --temp-c DEGREES --rain-mm MM  measured values, used when their weights are above 0
+  --snow-cm CM --humidity PCT --day-type weekday|weekend|holiday
+                                 further measured values for the regression models
+
+Models (predict):
+  --model nearest|linear|ridge|boosted
+                                 k nearest days (default), a regression on the daily measurements
+                                 or boosted trees on the hourly records; all but nearest need --month and --temp-c
+  --lambda X                     ridge penalty (default 1)
+  --trees N --tree-depth N --learning-rate X   boosted trees (default 150, 5, 0.1)
+  --save-model FILE --load-model FILE          keep the boosted trees between runs
+
+Hourly forecasts (predict-hours):
+  --forecast FILE                CSV/JSON with hour, temperature, humidity and optionally wind_speed,
+                                 rainfall, snowfall, visibility, solar_radiation, dew_point_temp
+  --date DATE | --month 1-12     the day (dd/mm/yyyy) or just its month; --day-type overrides the day type
+  --model nearest|boosted        nearest past hours (default, --k of them) or the boosted trees
+                                 the nearest hours use fixed weights; --similarity-config and --weight only apply to days
+
+Forecasting (forecast):
+  --days N                       how many days after the last one in the data
+  --forecast-method naive|holt-winters|weather|all   (default all)
+  --forecast FILE                CSV/JSON with date, temperature and optionally rainfall, snowfall,
+                                 humidity for the weather method; other days get their month's average
+  --output FILE                  also save the forecasts as CSV, or JSON for a .json file
+
+Evaluation (evaluate):
+  --scheme rolling|kfold         rolling origin (default) or k-fold cross-validation
+  --initial N --step N           rolling origin: training days at the first origin (default half)
+                                 and days tested after each origin (default 7)
+  --folds N                      k-fold: number of folds (default 5)
+  --model boosted                also evaluate the boosted trees, which takes a while
+
+Search:
+  --scope global|component|anchored   (default global)
+  --seeds N --depth N --max-score X   anchored search limits
+  --method bfs|dfs               closest-day search used by predict --batch (default bfs)
+  --k N --weighting inverse|uniform   nearest days in the estimate (default 5, inverse)
+  --coverage X                   share of days the intervals should cover (default 0.8)
+  --similarity-config FILE       load weights saved by fit-weights
+  --weight NAME=VALUE            override one weight, may be repeated
+  --graph exact|knn|threshold    graph to search (default exact)
+  --graph-k N --epsilon X        edge rule of the knn and threshold graphs
+  --exclude-date DATE --exclude-window N   never pick DATE or the N days on each side of it,
+                                 to estimate a past day honestly; evaluate leaves out the window
+                                 around every test day instead
+  --before DATE                  only pick days before DATE (not with evaluate)
+
+Other:
+  --group-by LIST                (stats) comma separated, e.g. season,weather or day-type
+  --batch FILE --output FILE     (predict) answer every conditions row of a CSV/JSON file
+  --output FILE                  (analyze) also save the tables as CSV, or JSON for a .json file
+  --format dot|graphml|json      (export) default: from the --output extension
+  --from DATE --to DATE          (graph path) dates as dd/mm/yyyy
+  --path-method astar|dijkstra   (graph path) default astar
+  --measure closeness|betweenness|pagerank   (graph representatives) default closeness
+  --groups components|communities --per-group N   (graph representatives) communities always use the --graph rule, knn with 5 neighbours by default
+
+Exit codes: 0 success, 1 failure while running, 2 invalid command line";
+
+//This enum is the subcommand to run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Stats,
+    Analyze,
+    Query,
+    Predict,
+    PredictHours,
+    Forecast,
+    Evaluate,
+    Export,
+    Graph(GraphAction),
+    FitWeights,
+    Interactive,
+    Help,
+}
+
+//This enum is the action of the graph subcommand
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GraphAction {
+    Analyze,
+    Path,
+    Representatives,
+}
+
+//This enum is the model the predict command uses
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModelChoice {
+    NearestDays,
+    //the ridge penalty, 0 being plain least squares
+    Regression(f64),
+    Boosted(BoostingConfig),
+}
+
+//This struct collects every flag, with defaults for those that were not given
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub data: String,
+    pub index: Option<String>,
+    pub predict: PredictConfig,
+    pub scope: Scope,
+    //the days the searches may not pick, and the window evaluate leaves out around each test day
+    pub exclusion: Exclusion,
+    pub exclude_window: i64,
+    pub similarity: SimilarityConfig,
+    //None is the original graph that links days with the same weather, temperature and precipitation
+    pub graph: Option<EdgeRule>,
+    //the conditions given with --month, --weather and so on
+    pub query: Option<Conditions>,
+    //the measured day given with --month, --temp-c and so on, for the regression models
+    pub day_query: Option<DayQuery>,
+    pub model: ModelChoice,
+    pub forecast: Option<String>,
+    //the month and day type of the hourly forecast
+    pub forecast_day: Option<(u32, DayType)>,
+    pub save_model: Option<String>,
+    pub load_model: Option<String>,
+    //how many days to forecast and with which methods
+    pub forecast_days: Option<usize>,
+    pub forecast_methods: Vec<ForecastMethod>,
+    pub scheme: Scheme,
+    pub path: Option<(String, String)>,
+    pub astar: bool,
+    pub measure: Centrality,
+    pub by_community: bool,
+    pub per_group: usize,
+    pub format: ExportFormat,
+    pub output: Option<String>,
+    pub batch: Option<String>,
+    pub depth_first: bool,
+    //empty prints one table per property, otherwise one table for the combination
+    pub group_by: Vec<Dimension>,
+}
+
+//This function parses a number flag and names the flag in the error
+fn number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("{} expects a number, got {}", flag, value))
+}
+
+//This function turns the arguments (without the program name) into the command and its options
+pub fn parse_args(args: &[String]) -> Result<(Command, Options), String> {
+    let mut rest = args.iter().map(String::as_str).peekable();
+    let command = match rest.next() {
+        None => return Err("No command given".to_string()),
+        Some("stats") => Command::Stats,
+        Some("analyze") => Command::Analyze,
+        Some("query") => Command::Query,
+        Some("predict") => Command::Predict,
+        Some("predict-hours") => Command::PredictHours,
+        Some("forecast") => Command::Forecast,
+        Some("evaluate") => Command::Evaluate,
+        Some("export") => Command::Export,
+        Some("graph") => match rest.next() {
+            Some("analyze") => Command::Graph(GraphAction::Analyze),
+            Some("path") => Command::Graph(GraphAction::Path),
+            Some("representatives") => Command::Graph(GraphAction::Representatives),
+            other => return Err(format!("graph needs analyze, path or representatives, got {}", other.unwrap_or("nothing"))),
+        },
+        Some("fit-weights") => Command::FitWeights,
+        Some("interactive") => Command::Interactive,
+        Some("help") | Some("--help") | Some("-h") => Command::Help,
+        Some(other) => return Err(format!("Unknown command: {}", other)),
+    };
+
+    let mut options = Options {
+        data: "SeoulBikeData 4.csv".to_string(),
+        index: None,
+        predict: PredictConfig::default(),
+        scope: Scope::Global,
+        exclusion: Exclusion::default(),
+        exclude_window: 0,
+        similarity: SimilarityConfig::default(),
+        graph: None,
+        query: None,
+        day_query: None,
+        model: ModelChoice::NearestDays,
+        forecast: None,
+        forecast_day: None,
+        save_model: None,
+        load_model: None,
+        forecast_days: None,
+        forecast_methods: ALL_METHODS.to_vec(),
+        scheme: Scheme::RollingOrigin { initial: None, step: 7 },
+        path: None,
+        astar: true,
+        measure: Centrality::Closeness,
+        by_community: false,
+        per_group: 1,
+        format: ExportFormat::Dot,
+        output: None,
+        batch: None,
+        depth_first: false,
+        group_by: Vec::new(),
+    };
+    let mut scope_name = "global".to_string();
+    let mut anchor = Anchor::default();
+    let mut similarity_path = None;
+    let mut weight_overrides = Vec::new();
+    let mut graph_name = "exact".to_string();
+    let mut graph_k = 5;
+    let mut epsilon = 10.0;
+    let mut format = None;
+    let (mut month, mut weather, mut temperature, mut precipitation, mut temperature_c, mut rainfall_mm) = (None, None, None, None, None, None);
+    let (mut from, mut to) = (None, None);
+    let (mut exclude_date, mut exclude_window, mut before): (Option<String>, Option<u32>, Option<String>) = (None, None, None);
+    let mut date: Option<String> = None;
+    let (mut snowfall_cm, mut humidity, mut day_type) = (None, None, None);
+    let (mut model, mut lambda) = ("nearest".to_string(), 1.0);
+    let mut boosting = BoostingConfig::default();
+    let (mut scheme_name, mut folds, mut initial, mut step) = ("rolling".to_string(), 5, None, 7);
+
+    while let Some(flag) = rest.next() {
+        let raw = rest.next().ok_or_else(|| format!("{} needs a value", flag))?;
+        let value = raw.to_lowercase();
+        match flag {
+            "--data" => options.data = raw.to_string(),
+            "--index" => options.index = Some(raw.to_string()),
+            "--month" => match number(flag, raw)? {
+                m @ 1..=12 => month = Some(m),
+                m => return Err(format!("--month has to be between 1 and 12, got {}", m)),
+            },
+            "--weather" => weather = Some(raw.parse()?),
+            "--temp" => temperature = Some(raw.parse()?),
+            "--precip" => precipitation = Some(raw.parse()?),
+            "--temp-c" => temperature_c = Some(number(flag, raw)?),
+            "--rain-mm" => rainfall_mm = Some(number(flag, raw)?),
+            "--snow-cm" => snowfall_cm = Some(number(flag, raw)?),
+            "--humidity" => humidity = Some(number(flag, raw)?),
+            "--day-type" => day_type = Some(raw.parse::<DayType>()?),
+            "--model" if ["nearest", "linear", "ridge", "boosted"].contains(&value.as_str()) => model = value,
+            "--lambda" => lambda = number(flag, raw)?,
+            "--trees" => boosting.trees = number(flag, raw)?,
+            "--tree-depth" => boosting.max_depth = number(flag, raw)?,
+            "--learning-rate" => boosting.learning_rate = number(flag, raw)?,
+            "--save-model" => options.save_model = Some(raw.to_string()),
+            "--load-model" => options.load_model = Some(raw.to_string()),
+            "--scope" if ["global", "component", "anchored"].contains(&value.as_str()) => scope_name = value,
+            "--seeds" => anchor.seeds = number(flag, raw)?,
+            "--depth" => anchor.max_depth = number(flag, raw)?,
+            "--max-score" => anchor.max_score = Some(number(flag, raw)?),
+            "--method" if value == "bfs" || value == "dfs" => options.depth_first = value == "dfs",
+            "--forecast-method" if value == "all" => options.forecast_methods = ALL_METHODS.to_vec(),
+            "--forecast-method" => options.forecast_methods = vec![raw.parse()?],
+            "--days" => options.forecast_days = Some(number(flag, raw)?),
+            "--scheme" if value == "rolling" || value == "kfold" => scheme_name = value,
+            "--folds" => folds = number(flag, raw)?,
+            "--initial" => initial = Some(number(flag, raw)?),
+            "--step" => step = number(flag, raw)?,
+            "--coverage" => options.predict.coverage = number(flag, raw)?,
+            "--k" => options.predict.k = number(flag, raw)?,
+            "--weighting" if value == "uniform" => options.predict.weighting = Weighting::Uniform,
+            "--weighting" if value == "inverse" => options.predict.weighting = Weighting::InverseScore,
+            "--similarity-config" => similarity_path = Some(raw.to_string()),
+            "--weight" => weight_overrides.push(value),
+            "--graph" if ["exact", "knn", "threshold"].contains(&value.as_str()) => graph_name = value,
+            "--graph-k" => graph_k = number(flag, raw)?,
+            "--epsilon" => epsilon = number(flag, raw)?,
+            "--batch" => options.batch = Some(raw.to_string()),
+            "--output" => options.output = Some(raw.to_string()),
+            "--format" => {
+                format = Some(match value.as_str() {
+                    "dot" => ExportFormat::Dot,
+                    "graphml" => ExportFormat::GraphMl,
+                    "json" => ExportFormat::Json,
+                    _ => return Err(format!("Unknown format: {}", raw)),
+                })
+            }
+            "--forecast" => options.forecast = Some(raw.to_string()),
+            "--date" => date = Some(raw.to_string()),
+            "--exclude-date" => exclude_date = Some(raw.to_string()),
+            "--exclude-window" => exclude_window = Some(number(flag, raw)?),
+            "--before" => before = Some(raw.to_string()),
+            "--from" => from = Some(raw.to_string()),
+            "--to" => to = Some(raw.to_string()),
+            "--path-method" if value == "astar" || value == "dijkstra" => options.astar = value == "astar",
+            "--measure" => {
+                options.measure = match value.as_str() {
+                    "closeness" => Centrality::Closeness,
+                    "betweenness" => Centrality::Betweenness,
+                    "pagerank" => Centrality::PageRank,
+                    _ => return Err(format!("Unknown centrality measure: {}", raw)),
+                }
+            }
+            "--groups" if value == "components" || value == "communities" => options.by_community = value == "communities",
+            "--per-group" => options.per_group = number(flag, raw)?,
+            "--group-by" => options.group_by = raw.split(',').map(str::parse).collect::<Result<_, _>>()?,
+            _ if flag.starts_with("--") => return Err(format!("Invalid value {} for {}", raw, flag)),
+            _ => return Err(format!("Unexpected argument: {}", flag)),
+        }
+    }
+
+    if options.predict.k == 0 || anchor.seeds == 0 || graph_k == 0 || options.per_group == 0 {
+        return Err("--k, --seeds, --graph-k and --per-group have to be at least 1".to_string());
+    }
+    if epsilon < 0.0 || lambda < 0.0 {
+        return Err("--epsilon and --lambda cannot be negative".to_string());
+    }
+    if options.forecast_days == Some(0) || !(options.predict.coverage > 0.0 && options.predict.coverage <= 1.0) {
+        return Err("--days has to be at least 1 and --coverage between 0 and 1".to_string());
+    }
+    if boosting.trees == 0 || !(boosting.learning_rate > 0.0 && boosting.learning_rate <= 1.0) {
+        return Err("--trees has to be at least 1 and --learning-rate between 0 and 1".to_string());
+    }
+    if folds < 2 || step == 0 || initial == Some(0) {
+        return Err("--folds has to be at least 2, --step and --initial at least 1".to_string());
+    }
+    options.scheme = if scheme_name == "kfold" { Scheme::KFold(folds) } else { Scheme::RollingOrigin { initial, step } };
+    options.model = match model.as_str() {
+        "linear" => ModelChoice::Regression(0.0),
+        "ridge" => ModelChoice::Regression(lambda),
+        "boosted" => ModelChoice::Boosted(boosting),
+        _ => ModelChoice::NearestDays,
+    };
+    options.scope = match scope_name.as_str() {
+        "component" => Scope::Component(0),
+        "anchored" => Scope::Anchored(anchor),
+        _ => Scope::Global,
+    };
+    options.graph = match graph_name.as_str() {
+        "knn" => Some(EdgeRule::Nearest(graph_k)),
+        "threshold" => Some(EdgeRule::Threshold(epsilon)),
+        _ => None,
+    };
+
+    //The file is loaded first so that the --weight overrides always win, whatever order they were given in
+    if let Some(path) = similarity_path {
+        options.similarity = load_config(&path).map_err(|e| format!("Failed to load similarity config {}: {}", path, e))?;
+    }
+    for assignment in weight_overrides {
+        let (key, value) = assignment
+            .split_once('=')
+            .ok_or_else(|| format!("--weight expects name=value, got {}", assignment))?;
+        options.similarity.set(key.trim(), number("--weight", value.trim())?)?;
+    }
+
+    if let (Some(month), Some(weather), Some(temperature)) = (month, weather.clone(), temperature.clone()) {
+        options.query = Some(Conditions {
+            month,
+            weather,
+            temperature,
+            precipitation: precipitation.unwrap_or(crate::common::PrecipitationIntensity::None),
+            temperature_c,
+            rainfall_mm,
+        });
+    } else if weather.is_some() || temperature.is_some() || (month.is_some() && temperature_c.is_none() && command != Command::PredictHours) {
+        return Err("--month, --weather and --temp have to be given together".to_string());
+    }
+    if let (Some(month), Some(temperature_c)) = (month, temperature_c) {
+        options.day_query = Some(DayQuery {
+            month,
+            day_type: day_type.unwrap_or(DayType::Weekday),
+            temperature_c,
+            rainfall_mm: rainfall_mm.unwrap_or(0.0),
+            snowfall_cm: snowfall_cm.unwrap_or(0.0),
+            humidity,
+        });
+    }
+    //A date gives both the month and whether it falls on a weekend; holidays have to be given with --day-type
+    let date_month = match &date {
+        Some(date) => match date.split('/').nth(1).and_then(|m| m.parse::<u32>().ok()) {
+            Some(m) if (1..=12).contains(&m) => Some((m, crate::model::day_type(date, "No Holiday"))),
+            _ => return Err(format!("--date expects dd/mm/yyyy, got {}", date)),
+        },
+        None => None,
+    };
+    options.forecast_day = match (date_month, month) {
+        (Some((m, weekday)), _) => Some((m, day_type.unwrap_or(weekday))),
+        (None, Some(m)) => Some((m, day_type.unwrap_or(DayType::Weekday))),
+        _ => None,
+    };
+    let day_of = |date: &str| crate::model::day_number(date).ok_or_else(|| format!("expected a dd/mm/yyyy date, got {}", date));
+    options.exclude_window = exclude_window.unwrap_or(0) as i64;
+    options.exclusion = Exclusion {
+        around: exclude_date.as_deref().map(day_of).transpose()?.map(|date| (date, options.exclude_window)),
+        before: before.as_deref().map(day_of).transpose()?,
+    };
+    if exclude_window.is_some() && exclude_date.is_none() && command != Command::Evaluate {
+        return Err("--exclude-window needs --exclude-date, except with evaluate".to_string());
+    }
+    //evaluate picks the day to leave out itself, once per test day, so a fixed date would be silently ignored
+    if command == Command::Evaluate && (exclude_date.is_some() || before.is_some()) {
+        return Err("evaluate leaves out the days around each test day itself and takes only --exclude-window".to_string());
+    }
+    if let (Some(from), Some(to)) = (from, to) {
+        options.path = Some((from, to));
+    }
+    options.format = match (format, options.output.as_deref()) {
+        (Some(format), _) => format,
+        (None, Some(path)) if path.to_lowercase().ends_with(".graphml") => ExportFormat::GraphMl,
+        (None, Some(path)) if path.to_lowercase().ends_with(".json") => ExportFormat::Json,
+        _ => ExportFormat::Dot,
+    };
+
+    //Each command checks it got what it needs here, before any data is loaded
+    match command {
+        Command::Query if options.query.is_none() => Err("query needs --month, --weather and --temp".to_string()),
+        Command::Predict if options.batch.is_some() && options.output.is_none() => Err("predict --batch needs --output FILE".to_string()),
+        Command::Predict if options.model != ModelChoice::NearestDays && options.day_query.is_none() => {
+            Err("predict with a regression or boosted model needs --month and --temp-c".to_string())
+        }
+        Command::Predict if options.model == ModelChoice::NearestDays && options.batch.is_none() && options.query.is_none() => {
+            Err("predict needs --month, --weather and --temp, or --batch FILE".to_string())
+        }
+        Command::PredictHours if options.forecast.is_none() || options.forecast_day.is_none() => {
+            Err("predict-hours needs --forecast FILE and --date DATE or --month".to_string())
+        }
+        Command::Forecast if options.forecast_days.is_none() => Err("forecast needs --days N".to_string()),
+        Command::PredictHours if matches!(options.model, ModelChoice::Regression(_)) => Err("predict-hours works with --model nearest or boosted".to_string()),
+        Command::Export | Command::FitWeights if options.output.is_none() => Err("this command needs --output FILE".to_string()),
+        Command::Graph(GraphAction::Path) if options.path.is_none() => Err("graph path needs --from DATE and --to DATE".to_string()),
+        _ => Ok((command, options)),
+    }
+}
+
+//The tests check a full query command line and that mistakes are errors instead of being ignored
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{PrecipitationIntensity, TemperatureCategory, Weather};
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let (command, options) = parse_args(&args("query --month 7 --weather Rainy --temp hot --precip heavy --k 3 --data other.csv --weight month=2")).unwrap();
+        assert_eq!(command, Command::Query);
+        let query = options.query.unwrap();
+        assert_eq!(query.month, 7);
+        assert_eq!(query.weather, Weather::Rainy);
+        assert_eq!(query.temperature, TemperatureCategory::Hot);
+        assert_eq!(query.precipitation, PrecipitationIntensity::Heavy);
+        assert_eq!(options.predict.k, 3);
+        assert_eq!(options.data, "other.csv");
+        assert_eq!(options.similarity.month, 2.0);
+
+        let (command, options) = parse_args(&args("export --output graph.graphml --graph knn")).unwrap();
+        assert_eq!(command, Command::Export);
+        assert_eq!(options.format, ExportFormat::GraphMl);
+        assert_eq!(options.graph, Some(EdgeRule::Nearest(5)));
+
+        let (_, options) = parse_args(&args("stats --group-by season,day-type")).unwrap();
+        assert_eq!(options.group_by, [Dimension::Season, Dimension::DayType]);
+
+        let (_, options) = parse_args(&args("predict --model ridge --lambda 2 --month 3 --temp-c 8.5 --day-type holiday")).unwrap();
+        assert_eq!(options.model, ModelChoice::Regression(2.0));
+        let day = options.day_query.unwrap();
+        assert_eq!((day.month, day.day_type, day.temperature_c, day.humidity), (3, DayType::Holiday, 8.5, None));
+
+        let (_, options) = parse_args(&args("predict-hours --forecast f.csv --date 09/06/2018 --model boosted")).unwrap();
+        assert_eq!(options.forecast_day, Some((6, DayType::Weekend)));
+
+        let (command, options) = parse_args(&args("forecast --days 14 --forecast-method holt-winters --coverage 0.9")).unwrap();
+        assert_eq!(command, Command::Forecast);
+        assert_eq!((options.forecast_days, options.forecast_methods, options.predict.coverage), (Some(14), vec![ForecastMethod::HoltWinters], 0.9));
+
+        let (_, options) = parse_args(&args("evaluate --scheme kfold --folds 10")).unwrap();
+        assert_eq!(options.scheme, Scheme::KFold(10));
+        let (_, options) = parse_args(&args("evaluate --step 14")).unwrap();
+        assert_eq!(options.scheme, Scheme::RollingOrigin { initial: None, step: 14 });
+
+        let (_, options) = parse_args(&args("predict --month 7 --weather sunny --temp hot --exclude-date 12/07/2018 --exclude-window 3 --before 01/08/2018")).unwrap();
+        let twelfth = crate::model::day_number("12/07/2018").unwrap();
+        assert_eq!(options.exclusion.around, Some((twelfth, 3)));
+        assert_eq!(options.exclusion.before, Some(twelfth + 20));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse_args(&[]).is_err());
+        assert!(parse_args(&args("forecast")).is_err());
+        assert!(parse_args(&args("forecast --days 7 --forecast-method arima")).is_err());
+        assert!(parse_args(&args("forecast --days 7 --method holt-winters")).is_err());
+        assert!(parse_args(&args("evaluate --scheme kfold --folds 1")).is_err());
+        assert!(parse_args(&args("query --month 7 --weather sunny --temp hot --exclude-window 3")).is_err());
+        assert!(parse_args(&args("evaluate --exclude-date 12/07/2018")).is_err());
+        assert!(parse_args(&args("evaluate --exclude-window 3 --before 01/08/2018")).is_err());
+        assert!(parse_args(&args("query --month 7 --weather sunny --temp hot --before 2018-07-01")).is_err());
+        assert!(parse_args(&args("query --month 13 --weather sunny --temp hot")).is_err());
+        assert!(parse_args(&args("query --month 7 --weather windy --temp hot")).is_err());
+        assert!(parse_args(&args("query --month 7")).is_err());
+        assert!(parse_args(&args("stats --verbose yes")).is_err());
+        assert!(parse_args(&args("stats --k")).is_err());
+        assert!(parse_args(&args("stats --group-by season,wind")).is_err());
+        assert!(parse_args(&args("graph path --from 01/01/2018")).is_err());
+        assert!(parse_args(&args("export")).is_err());
+        assert!(parse_args(&args("predict --model linear --month 3")).is_err());
+        assert_eq!(parse_args(&args("help")).unwrap().0, Command::Help);
+    }
+}
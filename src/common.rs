@@ -1,6 +1,7 @@
 // This module defines data structures, structs, enums, and types utilized across different modules
 use serde::Deserialize;
 use std::fmt;
+use std::str::FromStr;
 
 //The code on the next line was added since not all variables of struct RawRecord get used in the project so it generated warnings
 #[allow(dead_code)]
@@ -42,7 +43,7 @@ pub struct RawRecord {
 }
 
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 //This enum Weather initializes the four possible weather labels
 pub enum Weather {
     Sunny,
@@ -63,7 +64,22 @@ impl fmt::Display for Weather {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl FromStr for Weather {
+    type Err = String;
+
+    //reads the label back from text, ignoring case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "sunny" => Ok(Weather::Sunny),
+            "rainy" => Ok(Weather::Rainy),
+            "snowy" => Ok(Weather::Snowy),
+            "foggy" => Ok(Weather::Foggy),
+            _ => Err(format!("unknown weather: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 //This enum TemperatureCategory intialiazes different temperature groups for the raw records
 pub enum TemperatureCategory {
     Hot,
@@ -82,7 +98,20 @@ impl fmt::Display for TemperatureCategory {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl FromStr for TemperatureCategory {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "hot" => Ok(TemperatureCategory::Hot),
+            "moderate" => Ok(TemperatureCategory::Moderate),
+            "cold" => Ok(TemperatureCategory::Cold),
+            _ => Err(format!("unknown temperature: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 //The enum pricipitation intensity initializes three possible precipitation groups
 pub enum PrecipitationIntensity {
     Light,
@@ -101,6 +130,81 @@ impl fmt::Display for PrecipitationIntensity {
     }
 }
 
+impl FromStr for PrecipitationIntensity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "light" => Ok(PrecipitationIntensity::Light),
+            "heavy" => Ok(PrecipitationIntensity::Heavy),
+            "none" | "" => Ok(PrecipitationIntensity::None),
+            _ => Err(format!("unknown precipitation: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+//This enum separates working days from weekends and public holidays, which have very different demand
+pub enum DayType {
+    Weekday,
+    Weekend,
+    Holiday,
+}
+
+impl FromStr for DayType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "weekday" => Ok(DayType::Weekday),
+            "weekend" => Ok(DayType::Weekend),
+            "holiday" => Ok(DayType::Holiday),
+            _ => Err(format!("unknown day type: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for DayType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DayType::Weekday => write!(f, "Weekday"),
+            DayType::Weekend => write!(f, "Weekend"),
+            DayType::Holiday => write!(f, "Holiday"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+//This enum is the season a month falls in, spring being March to May
+pub enum Season {
+    Spring,
+    Summer,
+    Fall,
+    Winter,
+}
+
+impl Season {
+    pub fn of_month(month: u32) -> Season {
+        match month {
+            3..=5 => Season::Spring,
+            6..=8 => Season::Summer,
+            9..=11 => Season::Fall,
+            _ => Season::Winter,
+        }
+    }
+}
+
+impl fmt::Display for Season {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Season::Spring => write!(f, "Spring"),
+            Season::Summer => write!(f, "Summer"),
+            Season::Fall => write!(f, "Fall"),
+            Season::Winter => write!(f, "Winter"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 //This struct lists out traits that will be inputed by the user
 pub struct Conditions {
@@ -108,6 +212,9 @@ pub struct Conditions {
     pub weather: Weather,
     pub temperature: TemperatureCategory,
     pub precipitation: PrecipitationIntensity,
+    //Optional measured values, only used when their similarity weight is above 0
+    pub temperature_c: Option<f64>,
+    pub rainfall_mm: Option<f64>,
 }
 
 #[derive(Debug, Clone)]
@@ -119,4 +226,39 @@ pub struct DaySummary {
     pub weather: Weather,
     pub temperature_category: TemperatureCategory,
     pub precipitation: PrecipitationIntensity,
+    pub avg_temperature: f64,
+    pub total_rainfall: f64,
+    pub total_snowfall: f64,
+    pub avg_humidity: f64,
+    pub day_type: DayType,
+}
+
+//This struct is the small linear congruential generator behind the bootstrap intervals, the k-fold shuffle and the synthetic test days
+//It is seeded with a fixed number wherever it is used, so results repeat from run to run
+pub struct Lcg(pub u64);
+
+impl Lcg {
+    //This function gives the next number from 0 up to but not including modulo
+    pub fn below(&mut self, modulo: usize) -> usize {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        ((self.0 >> 33) % modulo as u64) as usize
+    }
+}
+
+//This function makes a sunny, moderate, dry weekday at 20°C for the tests, so each test only sets the fields it looks at
+#[cfg(test)]
+pub fn test_day(date: &str, total_rentals: i32) -> DaySummary {
+    DaySummary {
+        date: date.to_string(),
+        month: date.split('/').nth(1).and_then(|month| month.parse().ok()).unwrap_or(1),
+        total_rentals,
+        weather: Weather::Sunny,
+        temperature_category: TemperatureCategory::Moderate,
+        precipitation: PrecipitationIntensity::None,
+        avg_temperature: 20.0,
+        total_rainfall: 0.0,
+        total_snowfall: 0.0,
+        avg_humidity: 50.0,
+        day_type: DayType::Weekday,
+    }
 }
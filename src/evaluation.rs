@@ -0,0 +1,364 @@
+//This module measures how accurate every estimator is on days it was not trained on
+//Rolling origin trains on the days before each origin and tests on the days right after it, like using the model for real,
+//while k-fold trains on all but one of k groups of shuffled days and tests on the group left out
+use crate::common::{DaySummary, Lcg, Season};
+use crate::model::day_number;
+use crate::predictor::{DayQuery, Predictor};
+use crate::search::{bfs_closest, build_graph, build_weighted_graph, dfs_closest, predict, Adjacency, EdgeRule, Exclusion, PredictConfig, Scope};
+use crate::significance::BOOTSTRAP_SEED;
+use crate::similarity::{conditions_of, SimilarityConfig};
+use crate::stats::percentile;
+
+//This enum is how the days are split into training and test days
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scheme {
+    //the first origin is after `initial` days (half of them when None), then it moves `step` days at a time
+    RollingOrigin { initial: Option<usize>, step: usize },
+    KFold(usize),
+}
+
+//This enum is an estimator the evaluation can train on some days and test on others
+pub enum Candidate {
+    //the rentals of the closest day found by bfs or dfs
+    ClosestDay { depth_first: bool },
+    //the weighted estimate of the k nearest days, with its own interval
+    NearestDays,
+    Model(Box<dyn Predictor>),
+}
+
+//This struct holds the search settings the nearest-day candidates use, the same ones query and predict take
+pub struct SearchSettings<'a> {
+    pub scope: Scope,
+    pub similarity: &'a SimilarityConfig,
+    pub predict: &'a PredictConfig,
+    pub graph: Option<EdgeRule>,
+    //days on each side of a test day the searches may not pick, so its neighbours in time cannot give it away
+    pub window: i64,
+}
+
+//This struct is the estimate of one test day; the interval is None for estimators that do not give one
+#[derive(Debug, Clone, Copy)]
+struct Estimate {
+    mean: f64,
+    interval: Option<(f64, f64)>,
+}
+
+//This struct is the accuracy of one estimator over a set of test days
+//The percentage error leaves out days without rentals and coverage is None when there were no intervals
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metrics {
+    pub count: usize,
+    //test days the estimator gave no answer for, e.g. when no day matched
+    pub missed: usize,
+    pub mae: f64,
+    pub rmse: f64,
+    pub mape: f64,
+    pub coverage: Option<f64>,
+}
+
+//This struct is one line of the leaderboard with the same metrics per season
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub name: String,
+    pub overall: Metrics,
+    pub by_season: Vec<(Season, Metrics)>,
+    //why each fold the estimator could not be fitted on failed, as "fold n: reason"; its test days count as missed
+    pub failed_folds: Vec<String>,
+}
+
+//This struct is the leaderboard, best mean absolute error first
+#[derive(Debug, Clone)]
+pub struct Leaderboard {
+    pub scheme: Scheme,
+    pub folds: usize,
+    pub entries: Vec<Entry>,
+}
+
+impl Candidate {
+    pub fn name(&self, settings: &SearchSettings) -> String {
+        match self {
+            Candidate::ClosestDay { depth_first: false } => "bfs closest day".to_string(),
+            Candidate::ClosestDay { depth_first: true } => "dfs closest day".to_string(),
+            Candidate::NearestDays => format!("{} nearest days", settings.predict.k),
+            Candidate::Model(model) => model.name(),
+        }
+    }
+
+    //This function trains on the training days and estimates every test day
+    //It returns Err when a model cannot be fitted on the training days; the searches have nothing to fit and always answer
+    fn estimates(&mut self, train: &[DaySummary], test: &[DaySummary], settings: &SearchSettings) -> Result<Vec<Option<Estimate>>, String> {
+        match self {
+            Candidate::Model(model) => model_estimates(model.as_mut(), train, test, settings.predict.coverage)
+                .map_err(|e| format!("could not be fitted on {} training days: {}", train.len(), e)),
+            search => Ok(match settings.graph {
+                None => search.search_estimates(&build_graph(train), train, test, settings),
+                Some(rule) => search.search_estimates(&build_weighted_graph(train, settings.similarity, rule), train, test, settings),
+            }),
+        }
+    }
+
+    fn search_estimates<G: Adjacency>(&self, graph: &G, train: &[DaySummary], test: &[DaySummary], settings: &SearchSettings) -> Vec<Option<Estimate>> {
+        test.iter()
+            .map(|day| {
+                let query = conditions_of(day);
+                let exclusion = Exclusion { around: day_number(&day.date).map(|date| (date, settings.window)), before: None };
+                match self {
+                    Candidate::ClosestDay { depth_first } => {
+                        let closest = if *depth_first {
+                            dfs_closest(settings.scope, graph, train, &query, settings.similarity, &exclusion)
+                        } else {
+                            bfs_closest(settings.scope, graph, train, &query, settings.similarity, &exclusion)
+                        };
+                        closest.map(|result| Estimate { mean: result.day.total_rentals as f64, interval: None })
+                    }
+                    _ => predict(settings.scope, graph, train, &query, settings.similarity, settings.predict, &exclusion)
+                        .map(|prediction| Estimate { mean: prediction.mean, interval: Some((prediction.lower, prediction.upper)) }),
+                }
+            })
+            .collect()
+    }
+}
+
+//This function gives the errors the model makes on the latest fifth of the training days after fitting on the rest
+//Errors on the days the model was fitted on are too small, boosted trees nearly memorise them, so intervals use these instead
+fn holdout_errors(model: &mut dyn Predictor, train: &[DaySummary]) -> Vec<f64> {
+    let mut ordered: Vec<&DaySummary> = train.iter().collect();
+    ordered.sort_by_key(|day| day_number(&day.date));
+    let split = ordered.len() - ordered.len() / HOLDOUT_SHARE;
+    let fitted: Vec<DaySummary> = ordered[..split].iter().map(|&day| day.clone()).collect();
+    if split == ordered.len() || model.fit(&fitted).is_err() {
+        return Vec::new();
+    }
+    let mut errors: Vec<f64> = ordered[split..]
+        .iter()
+        .filter_map(|day| model.predict(&DayQuery::of_day(day)).map(|estimate| day.total_rentals as f64 - estimate))
+        .collect();
+    errors.sort_by(f64::total_cmp);
+    errors
+}
+
+//This function fits the model and estimates the test days
+//The models give no interval of their own, so theirs is spread like the errors the model makes on held-out training days,
+//and left out when there are too few training days to hold any out
+fn model_estimates(model: &mut dyn Predictor, train: &[DaySummary], test: &[DaySummary], coverage: f64) -> Result<Vec<Option<Estimate>>, String> {
+    let errors = holdout_errors(model, train);
+    let tail = (1.0 - coverage) / 2.0;
+    let spread = if errors.is_empty() { None } else { Some((percentile(&errors, tail), percentile(&errors, 1.0 - tail))) };
+    model.fit(train)?;
+    Ok(test
+        .iter()
+        .map(|day| {
+            model.predict(&DayQuery::of_day(day)).map(|estimate| Estimate {
+                mean: estimate.max(0.0),
+                interval: spread.map(|(below, above)| ((estimate + below).max(0.0), (estimate + above).max(0.0))),
+            })
+        })
+        .collect())
+}
+
+//The latest 1/HOLDOUT_SHARE of the training days are held out to measure the spread of a model's errors
+const HOLDOUT_SHARE: usize = 5;
+
+//Pairs of training and test days, one pair per fold
+type Folds = Vec<(Vec<DaySummary>, Vec<DaySummary>)>;
+
+//This function splits the days into the folds of the scheme
+fn folds(days: &[DaySummary], scheme: Scheme) -> Result<Folds, String> {
+    let mut ordered = days
+        .iter()
+        .map(|day| day_number(&day.date).map(|number| (number, day.clone())).ok_or_else(|| format!("{} is not a dd/mm/yyyy date", day.date)))
+        .collect::<Result<Vec<_>, _>>()?;
+    ordered.sort_by_key(|(number, _)| *number);
+    let ordered: Vec<DaySummary> = ordered.into_iter().map(|(_, day)| day).collect();
+    let n = ordered.len();
+    match scheme {
+        Scheme::RollingOrigin { initial, step } => {
+            let initial = initial.unwrap_or(n / 2);
+            if initial == 0 || initial >= n || step == 0 {
+                return Err(format!("rolling origin needs between 1 and {} training days and a step of at least 1", n.saturating_sub(1)));
+            }
+            Ok((initial..n).step_by(step).map(|origin| (ordered[..origin].to_vec(), ordered[origin..(origin + step).min(n)].to_vec())).collect())
+        }
+        Scheme::KFold(k) => {
+            if k < 2 || k > n {
+                return Err(format!("k-fold needs between 2 and {} folds", n));
+            }
+            //Fisher-Yates with the bootstrap generator, so the folds are the same from run to run
+            let mut resampler = Lcg(BOOTSTRAP_SEED);
+            let mut shuffled = ordered;
+            for i in (1..n).rev() {
+                shuffled.swap(i, resampler.below(i + 1));
+            }
+            Ok((0..k)
+                .map(|fold| {
+                    let train = shuffled.iter().enumerate().filter(|(i, _)| i % k != fold).map(|(_, day)| day.clone()).collect();
+                    (train, shuffled.iter().skip(fold).step_by(k).cloned().collect())
+                })
+                .collect())
+        }
+    }
+}
+
+//This function computes the metrics of the estimates, given as (actual rentals, estimate) pairs
+fn metrics(outcomes: &[(f64, Option<Estimate>)]) -> Metrics {
+    let answered: Vec<(f64, Estimate)> = outcomes.iter().filter_map(|(actual, estimate)| estimate.map(|e| (*actual, e))).collect();
+    let count = answered.len();
+    let mean = |values: Vec<f64>| if values.is_empty() { 0.0 } else { values.iter().sum::<f64>() / values.len() as f64 };
+    let with_interval: Vec<bool> = answered.iter().filter_map(|(actual, e)| e.interval.map(|(lower, upper)| lower <= *actual && *actual <= upper)).collect();
+    Metrics {
+        count,
+        missed: outcomes.len() - count,
+        mae: mean(answered.iter().map(|(actual, e)| (actual - e.mean).abs()).collect()),
+        rmse: mean(answered.iter().map(|(actual, e)| (actual - e.mean).powi(2)).collect()).sqrt(),
+        mape: 100.0 * mean(answered.iter().filter(|(actual, _)| *actual > 0.0).map(|(actual, e)| ((actual - e.mean) / actual).abs()).collect()),
+        coverage: if with_interval.is_empty() { None } else { Some(with_interval.iter().filter(|&&inside| inside).count() as f64 / with_interval.len() as f64) },
+    }
+}
+
+//This function trains and tests every candidate on every fold and ranks them by mean absolute error
+pub fn evaluate(days: &[DaySummary], candidates: &mut [Candidate], scheme: Scheme, settings: &SearchSettings) -> Result<Leaderboard, String> {
+    let folds = folds(days, scheme)?;
+    let mut entries = Vec::with_capacity(candidates.len());
+    for candidate in candidates.iter_mut() {
+        let mut outcomes: Vec<(Season, f64, Option<Estimate>)> = Vec::new();
+        let mut failed_folds = Vec::new();
+        for (fold, (train, test)) in folds.iter().enumerate() {
+            //a fold the model cannot be fitted on leaves all of its test days without an estimate, so they count as missed
+            let estimates = candidate.estimates(train, test, settings).unwrap_or_else(|e| {
+                failed_folds.push(format!("fold {}: {}", fold + 1, e));
+                vec![None; test.len()]
+            });
+            outcomes.extend(test.iter().zip(estimates).map(|(day, estimate)| (Season::of_month(day.month), day.total_rentals as f64, estimate)));
+        }
+        let pairs = |season: Option<Season>| -> Vec<(f64, Option<Estimate>)> {
+            outcomes.iter().filter(|(s, _, _)| season.is_none_or(|wanted| *s == wanted)).map(|(_, actual, estimate)| (*actual, *estimate)).collect()
+        };
+        let by_season = [Season::Spring, Season::Summer, Season::Fall, Season::Winter]
+            .into_iter()
+            .map(|season| (season, metrics(&pairs(Some(season)))))
+            .filter(|(_, m)| m.count + m.missed > 0)
+            .collect();
+        entries.push(Entry { name: candidate.name(settings), overall: metrics(&pairs(None)), by_season, failed_folds });
+    }
+    entries.sort_by(|a, b| a.overall.mae.total_cmp(&b.overall.mae));
+    Ok(Leaderboard { scheme, folds: folds.len(), entries })
+}
+
+fn show_coverage(coverage: Option<f64>) -> String {
+    coverage.map_or("-".to_string(), |c| format!("{:.1}%", c * 100.0))
+}
+
+//This function prints the leaderboard and then the same metrics for every season
+pub fn print_leaderboard(board: &Leaderboard, coverage: f64) {
+    let scheme = match board.scheme {
+        Scheme::RollingOrigin { step, .. } => format!("rolling origin, {} days ahead", step),
+        Scheme::KFold(k) => format!("{}-fold cross-validation", k),
+    };
+    println!("[Leaderboard: {}, {} folds, intervals aiming at {:.0}%]", scheme, board.folds, coverage * 100.0);
+    println!("  {:<4} {:<36} {:>6} {:>8} {:>8} {:>7} {:>9}", "Rank", "Estimator", "Days", "MAE", "RMSE", "MAPE", "Coverage");
+    for (rank, entry) in board.entries.iter().enumerate() {
+        let m = &entry.overall;
+        println!("  {:<4} {:<36} {:>6} {:>8.0} {:>8.0} {:>6.1}% {:>9}", rank + 1, entry.name, m.count, m.mae, m.rmse, m.mape, show_coverage(m.coverage));
+        if m.missed > 0 {
+            println!("       ({} test days had no estimate)", m.missed);
+        }
+        if !entry.failed_folds.is_empty() {
+            println!("       ({} of {} folds could not be fitted)", entry.failed_folds.len(), board.folds);
+            for failure in &entry.failed_folds {
+                println!("         {}", failure);
+            }
+        }
+    }
+    println!();
+    println!("[By season]");
+    for entry in &board.entries {
+        println!("  {}", entry.name);
+        for (season, m) in &entry.by_season {
+            println!("    {:<8} {:>6} {:>8.0} {:>8.0} {:>6.1}% {:>9}", season.to_string(), m.count, m.mae, m.rmse, m.mape, show_coverage(m.coverage));
+        }
+    }
+}
+
+//The tests check that rolling origin never trains on later days, that k-fold tests every day once, and the metrics
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_day;
+    use crate::model::date_of;
+
+    fn make_day(number: i64, total_rentals: i32) -> DaySummary {
+        test_day(&date_of(number), total_rentals)
+    }
+
+    #[test]
+    fn test_folds_and_metrics() {
+        let start = day_number("01/03/2018").unwrap();
+        //listed newest first so the split has to sort them
+        let days: Vec<DaySummary> = (0..20).rev().map(|i| make_day(start + i, 1000 + i as i32)).collect();
+        let rolling = folds(&days, Scheme::RollingOrigin { initial: Some(12), step: 3 }).unwrap();
+        assert_eq!(rolling.len(), 3);
+        assert_eq!(rolling[2].1.len(), 2);
+        for (train, test) in &rolling {
+            let last_trained = train.iter().map(|d| day_number(&d.date).unwrap()).max().unwrap();
+            assert!(test.iter().all(|d| day_number(&d.date).unwrap() > last_trained));
+        }
+        let kfold = folds(&days, Scheme::KFold(4)).unwrap();
+        let mut tested: Vec<i32> = kfold.iter().flat_map(|(_, test)| test.iter().map(|d| d.total_rentals)).collect();
+        tested.sort();
+        assert_eq!(tested, (1000..1020).collect::<Vec<i32>>());
+        assert!(kfold.iter().all(|(train, test)| train.len() + test.len() == 20));
+        assert!(folds(&days, Scheme::KFold(1)).is_err());
+
+        let estimate = |mean: f64, interval: Option<(f64, f64)>| Some(Estimate { mean, interval });
+        let outcomes = [(100.0, estimate(110.0, Some((90.0, 120.0)))), (200.0, estimate(170.0, Some((150.0, 190.0)))), (50.0, None)];
+        let m = metrics(&outcomes);
+        assert_eq!((m.count, m.missed), (2, 1));
+        assert_eq!(m.mae, 20.0);
+        assert!((m.rmse - 500.0f64.sqrt()).abs() < 1e-9);
+        assert!((m.mape - 12.5).abs() < 1e-9);
+        assert_eq!(m.coverage, Some(0.5));
+    }
+
+    //Predicts the mean rentals of its training days, and cannot be fitted on fewer than ten days
+    struct MeanOfTen(Option<f64>);
+
+    impl Predictor for MeanOfTen {
+        fn name(&self) -> String {
+            "mean of at least ten days".to_string()
+        }
+
+        fn fit(&mut self, days: &[DaySummary]) -> Result<(), String> {
+            if days.len() < 10 {
+                return Err("too few days".to_string());
+            }
+            self.0 = Some(days.iter().map(|d| d.total_rentals as f64).sum::<f64>() / days.len() as f64);
+            Ok(())
+        }
+
+        fn predict(&self, _: &DayQuery) -> Option<f64> {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_failed_fits_are_missed() {
+        let start = day_number("01/03/2018").unwrap();
+        let days: Vec<DaySummary> = (0..20).map(|i| make_day(start + i, 1000 + i as i32)).collect();
+        let similarity = SimilarityConfig::default();
+        let predict = PredictConfig::default();
+        let settings = SearchSettings { scope: Scope::Global, similarity: &similarity, predict: &predict, graph: None, window: 0 };
+        let mut candidates = vec![Candidate::Model(Box::new(MeanOfTen(None)))];
+        //training on 5, 10 and 15 days: the first fold cannot be fitted, and the second has too few days to hold out 2 and still fit
+        let board = evaluate(&days, &mut candidates, Scheme::RollingOrigin { initial: Some(5), step: 5 }, &settings).unwrap();
+        let m = &board.entries[0].overall;
+        assert_eq!((m.count, m.missed), (10, 5));
+        assert_eq!(board.entries[0].failed_folds, ["fold 1: could not be fitted on 5 training days: too few days"]);
+        assert!(m.coverage.is_some());
+
+        let mut model = MeanOfTen(None);
+        let estimates = model_estimates(&mut model, &days[..15], &days[15..], 0.9).unwrap();
+        //the held-out days 12 to 14 miss the mean of days 0 to 11 by 6.5 to 8.5 rentals, so the interval sits above the estimate
+        let (lower, upper) = estimates[0].unwrap().interval.unwrap();
+        assert!(lower > 1007.0 + 6.0 && upper < 1007.0 + 9.0);
+    }
+}
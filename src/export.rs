@@ -0,0 +1,251 @@
+//This module writes the similarity graphs to files that Graphviz and Gephi can open: DOT, GraphML and a JSON node/edge list
+use crate::common::DaySummary;
+use crate::search::{Graph, WeightedGraph};
+use serde::Serialize;
+use std::error::Error;
+use std::fs;
+
+//This enum lists the supported file formats
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Dot,
+    GraphMl,
+    Json,
+}
+
+//One undirected edge. The exact-match graph has no weights, so distance and similarity are left out for it
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportEdge {
+    pub source: usize,
+    pub target: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distance: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub similarity: Option<f64>,
+}
+
+//One day with the attributes shown on its node
+#[derive(Debug, Clone, Serialize)]
+struct ExportNode<'a> {
+    id: usize,
+    date: &'a str,
+    month: u32,
+    rentals: i32,
+    weather: String,
+    temperature: String,
+    precipitation: String,
+    avg_temperature: f64,
+    total_rainfall: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct ExportDocument<'a> {
+    directed: bool,
+    nodes: Vec<ExportNode<'a>>,
+    edges: &'a [ExportEdge],
+}
+
+//This function lists every edge of the exact-match graph once, which is every pair inside each class
+pub fn exact_edges(graph: &Graph) -> Vec<ExportEdge> {
+    let mut edges = Vec::new();
+    for class in &graph.classes {
+        for (position, &source) in class.iter().enumerate() {
+            for &target in &class[position + 1..] {
+                edges.push(ExportEdge { source, target, distance: None, similarity: None });
+            }
+        }
+    }
+    edges
+}
+
+//This function lists every edge of the weighted graph once, with its distance and similarity
+pub fn weighted_edges(graph: &WeightedGraph) -> Vec<ExportEdge> {
+    let mut edges: Vec<ExportEdge> = graph
+        .adj_list
+        .iter()
+        .flat_map(|(&source, list)| {
+            list.iter().filter(move |edge| source < edge.node).map(move |edge| ExportEdge {
+                source,
+                target: edge.node,
+                distance: Some(edge.distance),
+                similarity: Some(edge.similarity()),
+            })
+        })
+        .collect();
+    edges.sort_by_key(|edge| (edge.source, edge.target));
+    edges
+}
+
+fn nodes(days: &[DaySummary]) -> Vec<ExportNode<'_>> {
+    days.iter()
+        .enumerate()
+        .map(|(id, day)| ExportNode {
+            id,
+            date: &day.date,
+            month: day.month,
+            rentals: day.total_rentals,
+            weather: day.weather.to_string(),
+            temperature: day.temperature_category.to_string(),
+            precipitation: day.precipitation.to_string(),
+            avg_temperature: day.avg_temperature,
+            total_rainfall: day.total_rainfall,
+        })
+        .collect()
+}
+
+//Escapes the characters that would end a quoted DOT string, where a backslash starts an escape sequence
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+//Escapes the characters that would break XML text in GraphML
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+//This function writes the graph in the Graphviz DOT language, labelling each node with its date and rentals
+pub fn to_dot(days: &[DaySummary], edges: &[ExportEdge]) -> String {
+    let mut out = String::from("graph days {\n  node [shape=box];\n");
+    for node in nodes(days) {
+        out.push_str(&format!(
+            "  {} [label=\"{}\\n{} rentals\" date=\"{}\" month={} rentals={} weather=\"{}\" temperature=\"{}\" precipitation=\"{}\" avg_temperature={:.2} total_rainfall={:.2}];\n",
+            node.id,
+            escape_dot(node.date),
+            node.rentals,
+            escape_dot(node.date),
+            node.month,
+            node.rentals,
+            escape_dot(&node.weather),
+            escape_dot(&node.temperature),
+            escape_dot(&node.precipitation),
+            node.avg_temperature,
+            node.total_rainfall
+        ));
+    }
+    for edge in edges {
+        match (edge.distance, edge.similarity) {
+            (Some(distance), Some(similarity)) => out.push_str(&format!(
+                "  {} -- {} [distance={:.4} weight={:.4}];\n",
+                edge.source, edge.target, distance, similarity
+            )),
+            _ => out.push_str(&format!("  {} -- {};\n", edge.source, edge.target)),
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+//This function writes the graph as GraphML, declaring every attribute with its type so Gephi can use them
+pub fn to_graphml(days: &[DaySummary], edges: &[ExportEdge]) -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+    );
+    let keys = [
+        ("date", "node", "string"),
+        ("month", "node", "int"),
+        ("rentals", "node", "int"),
+        ("weather", "node", "string"),
+        ("temperature", "node", "string"),
+        ("precipitation", "node", "string"),
+        ("avg_temperature", "node", "double"),
+        ("total_rainfall", "node", "double"),
+        ("distance", "edge", "double"),
+        ("weight", "edge", "double"),
+    ];
+    for (name, target, kind) in keys {
+        out.push_str(&format!("  <key id=\"{0}\" for=\"{1}\" attr.name=\"{0}\" attr.type=\"{2}\"/>\n", name, target, kind));
+    }
+    out.push_str("  <graph id=\"days\" edgedefault=\"undirected\">\n");
+    for node in nodes(days) {
+        out.push_str(&format!("    <node id=\"n{}\">\n", node.id));
+        let values = [
+            ("date", escape_xml(node.date)),
+            ("month", node.month.to_string()),
+            ("rentals", node.rentals.to_string()),
+            ("weather", node.weather),
+            ("temperature", node.temperature),
+            ("precipitation", node.precipitation),
+            ("avg_temperature", node.avg_temperature.to_string()),
+            ("total_rainfall", node.total_rainfall.to_string()),
+        ];
+        for (key, value) in values {
+            out.push_str(&format!("      <data key=\"{}\">{}</data>\n", key, value));
+        }
+        out.push_str("    </node>\n");
+    }
+    for edge in edges {
+        out.push_str(&format!("    <edge source=\"n{}\" target=\"n{}\"", edge.source, edge.target));
+        match (edge.distance, edge.similarity) {
+            (Some(distance), Some(similarity)) => out.push_str(&format!(
+                ">\n      <data key=\"distance\">{}</data>\n      <data key=\"weight\">{}</data>\n    </edge>\n",
+                distance, similarity
+            )),
+            _ => out.push_str("/>\n"),
+        }
+    }
+    out.push_str("  </graph>\n</graphml>\n");
+    out
+}
+
+//This function writes the graph as {"directed": false, "nodes": [...], "edges": [...]}
+pub fn to_json(days: &[DaySummary], edges: &[ExportEdge]) -> Result<String, Box<dyn Error>> {
+    let document = ExportDocument { directed: false, nodes: nodes(days), edges };
+    Ok(serde_json::to_string_pretty(&document)?)
+}
+
+//This function writes the graph to path in the chosen format
+pub fn export_graph(path: &str, format: ExportFormat, days: &[DaySummary], edges: &[ExportEdge]) -> Result<(), Box<dyn Error>> {
+    let text = match format {
+        ExportFormat::Dot => to_dot(days, edges),
+        ExportFormat::GraphMl => to_graphml(days, edges),
+        ExportFormat::Json => to_json(days, edges)?,
+    };
+    fs::write(path, text)?;
+    Ok(())
+}
+
+//The test exports a small graph in every format and checks the node attributes and edges come through
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{test_day, TemperatureCategory, Weather};
+    use crate::search::build_graph;
+
+    fn days() -> Vec<DaySummary> {
+        ["01/07/2018", "02/07/2018", "03/07/2018"]
+            .iter()
+            .enumerate()
+            .map(|(i, date)| DaySummary {
+                weather: if i == 2 { Weather::Rainy } else { Weather::Sunny },
+                temperature_category: TemperatureCategory::Hot,
+                avg_temperature: 27.5,
+                ..test_day(date, 20000 + i as i32)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_export_formats() {
+        let days = days();
+        let edges = exact_edges(&build_graph(&days));
+        assert_eq!(edges.len(), 1);
+
+        let dot = to_dot(&days, &edges);
+        assert!(dot.starts_with("graph days {"));
+        assert!(dot.contains("0 -- 1;"));
+        assert!(dot.contains("weather=\"Rainy\""));
+        assert_eq!(escape_dot("a \"b\" \\ c"), "a \\\"b\\\" \\\\ c");
+        assert_eq!(escape_xml("a \"b\" & c"), "a &quot;b&quot; &amp; c");
+
+        let graphml = to_graphml(&days, &edges);
+        assert!(graphml.contains("<edge source=\"n0\" target=\"n1\"/>"));
+        assert!(graphml.contains("<data key=\"rentals\">20002</data>"));
+
+        let json: serde_json::Value = serde_json::from_str(&to_json(&days, &edges).unwrap()).unwrap();
+        assert_eq!(json["nodes"].as_array().unwrap().len(), 3);
+        assert_eq!(json["nodes"][1]["date"], "02/07/2018");
+        assert_eq!(json["edges"][0]["target"], 1);
+        assert!(json["edges"][0].get("distance").is_none());
+    }
+}
@@ -0,0 +1,405 @@
+//This module forecasts the rentals of the days after the data ends, treating the days as one chronological series
+//Seasonal naive repeats the last week, Holt-Winters follows the level, trend and weekly pattern,
+//and the weather-adjusted variant runs Holt-Winters on what the weather regression does not explain
+use crate::common::{DaySummary, DayType};
+use crate::table::{read_rows, write_rows};
+use crate::model::{date_of, day_number, day_type};
+use crate::predictor::{DayQuery, Predictor, RegressionPredictor};
+use crate::stats::percentile;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+//Demand repeats every week, so the seasonal pattern has one value per day of the week
+pub const SEASON_LENGTH: usize = 7;
+
+//This enum is the way the next days are forecast
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ForecastMethod {
+    SeasonalNaive,
+    HoltWinters,
+    WeatherAdjusted,
+}
+
+pub const ALL_METHODS: [ForecastMethod; 3] = [ForecastMethod::SeasonalNaive, ForecastMethod::HoltWinters, ForecastMethod::WeatherAdjusted];
+
+impl fmt::Display for ForecastMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ForecastMethod::SeasonalNaive => write!(f, "seasonal naive"),
+            ForecastMethod::HoltWinters => write!(f, "Holt-Winters"),
+            ForecastMethod::WeatherAdjusted => write!(f, "weather-adjusted Holt-Winters"),
+        }
+    }
+}
+
+impl FromStr for ForecastMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "naive" | "seasonal-naive" => Ok(ForecastMethod::SeasonalNaive),
+            "holt-winters" | "hw" => Ok(ForecastMethod::HoltWinters),
+            "weather" | "weather-adjusted" => Ok(ForecastMethod::WeatherAdjusted),
+            _ => Err(format!("unknown forecast method: {}", s)),
+        }
+    }
+}
+
+//This struct is the weather expected on a future day; what is left out is taken from the same month in the past
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DayWeather {
+    pub date: String,
+    pub temperature: f64,
+    #[serde(default)]
+    pub rainfall: f64,
+    #[serde(default)]
+    pub snowfall: f64,
+    #[serde(default)]
+    pub humidity: Option<f64>,
+}
+
+//This struct is the rentals of every calendar day from the first to the last day of the data
+//Days missing from the data (the bikes were not running) are filled in with the same weekday of the week before
+#[derive(Debug, Clone)]
+pub struct DailySeries {
+    pub start: i64,
+    pub values: Vec<f64>,
+    pub filled: usize,
+}
+
+//This struct is the forecast of one future day
+#[derive(Debug, Clone)]
+pub struct ForecastDay {
+    pub date: String,
+    pub day_type: DayType,
+    pub mean: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+//This struct is the forecast of one method, with the smoothing parameters when Holt-Winters chose them
+#[derive(Debug, Clone)]
+pub struct Forecast {
+    pub method: ForecastMethod,
+    pub days: Vec<ForecastDay>,
+    //standard deviation of the one-step-ahead errors on the history
+    pub residual_sd: f64,
+    pub smoothing: Option<Smoothing>,
+}
+
+//This struct is the three smoothing parameters of Holt-Winters, each between 0 and 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Smoothing {
+    pub alpha: f64,
+    pub beta: f64,
+    pub gamma: f64,
+}
+
+//This function fills every gap with the value a week earlier, or the value just before when there is none
+fn fill_gaps(values: &[Option<f64>]) -> Vec<f64> {
+    let mut filled: Vec<f64> = Vec::with_capacity(values.len());
+    for (t, value) in values.iter().enumerate() {
+        let fallback = if t >= SEASON_LENGTH { filled[t - SEASON_LENGTH] } else { filled.last().copied().unwrap_or(0.0) };
+        filled.push(value.unwrap_or(fallback));
+    }
+    filled
+}
+
+//This function lays the days out on the calendar, ordered by date
+pub fn daily_series(days: &[DaySummary]) -> Result<DailySeries, String> {
+    let numbered = numbered_days(days)?;
+    let (start, end) = (numbered[0].0, numbered[numbered.len() - 1].0);
+    let mut values = vec![None; (end - start + 1) as usize];
+    for (number, day) in &numbered {
+        values[(number - start) as usize] = Some(day.total_rentals as f64);
+    }
+    let filled = values.iter().filter(|value| value.is_none()).count();
+    let series = DailySeries { start, values: fill_gaps(&values), filled };
+    if series.values.len() < 2 * SEASON_LENGTH {
+        return Err(format!("forecasting needs at least {} consecutive days, the data covers {}", 2 * SEASON_LENGTH, series.values.len()));
+    }
+    Ok(series)
+}
+
+//This function pairs every day with its day number, in date order
+fn numbered_days(days: &[DaySummary]) -> Result<Vec<(i64, &DaySummary)>, String> {
+    let mut numbered = days
+        .iter()
+        .map(|day| day_number(&day.date).map(|number| (number, day)).ok_or_else(|| format!("{} is not a dd/mm/yyyy date", day.date)))
+        .collect::<Result<Vec<_>, _>>()?;
+    if numbered.is_empty() {
+        return Err("no days to forecast from".to_string());
+    }
+    numbered.sort_by_key(|(number, _)| *number);
+    Ok(numbered)
+}
+
+//This function gives the offsets of the interval around a forecast from the errors the method made on the history
+//The errors are scaled up by the square root of how much the variance has grown at that horizon
+fn interval(sorted_errors: &[f64], variance_growth: f64, coverage: f64) -> (f64, f64) {
+    let tail = (1.0 - coverage.clamp(0.0, 1.0)) / 2.0;
+    let scale = variance_growth.sqrt();
+    (percentile(sorted_errors, tail) * scale, percentile(sorted_errors, 1.0 - tail) * scale)
+}
+
+fn sorted(mut errors: Vec<f64>) -> Vec<f64> {
+    errors.sort_by(f64::total_cmp);
+    errors
+}
+
+fn standard_deviation(errors: &[f64]) -> f64 {
+    if errors.is_empty() {
+        return 0.0;
+    }
+    let mean = errors.iter().sum::<f64>() / errors.len() as f64;
+    (errors.iter().map(|e| (e - mean).powi(2)).sum::<f64>() / errors.len() as f64).sqrt()
+}
+
+//This function labels the future days, which are taken to be ordinary days since the holidays ahead are not known
+fn future_day(series: &DailySeries, h: usize) -> (String, DayType) {
+    let date = date_of(series.start + (series.values.len() + h - 1) as i64);
+    let day_type = day_type(&date, "No Holiday");
+    (date, day_type)
+}
+
+//This function forecasts every day as the same weekday of the last week in the data
+pub fn seasonal_naive(series: &DailySeries, horizon: usize, coverage: f64) -> Forecast {
+    let values = &series.values;
+    let n = values.len();
+    let errors: Vec<f64> = (SEASON_LENGTH..n).map(|t| values[t] - values[t - SEASON_LENGTH]).collect();
+    let residual_sd = standard_deviation(&errors);
+    let errors = sorted(errors);
+    let days = (1..=horizon)
+        .map(|h| {
+            let (date, day_type) = future_day(series, h);
+            let mean = values[n - SEASON_LENGTH + (h - 1) % SEASON_LENGTH];
+            //every week further ahead adds one more week of errors
+            let (lower, upper) = interval(&errors, ((h - 1) / SEASON_LENGTH + 1) as f64, coverage);
+            ForecastDay { date, day_type, mean, lower: (mean + lower).max(0.0), upper: (mean + upper).max(0.0) }
+        })
+        .collect();
+    Forecast { method: ForecastMethod::SeasonalNaive, days, residual_sd, smoothing: None }
+}
+
+//This struct is the state of additive Holt-Winters after going through the series
+struct HoltWintersState {
+    level: f64,
+    trend: f64,
+    //seasonal[t % 7] is the weekly effect of day t
+    seasonal: Vec<f64>,
+    errors: Vec<f64>,
+}
+
+//This function runs additive Holt-Winters over the series, starting from the averages of the first two weeks
+fn run_holt_winters(values: &[f64], smoothing: Smoothing) -> HoltWintersState {
+    let m = SEASON_LENGTH;
+    let first = values[..m].iter().sum::<f64>() / m as f64;
+    let second = values[m..2 * m].iter().sum::<f64>() / m as f64;
+    let mut state = HoltWintersState {
+        level: first,
+        trend: (second - first) / m as f64,
+        seasonal: values[..m].iter().map(|value| value - first).collect(),
+        errors: Vec::with_capacity(values.len()),
+    };
+    let Smoothing { alpha, beta, gamma } = smoothing;
+    for (t, &value) in values.iter().enumerate().skip(m) {
+        let season = state.seasonal[t % m];
+        state.errors.push(value - (state.level + state.trend + season));
+        let level = alpha * (value - season) + (1.0 - alpha) * (state.level + state.trend);
+        state.trend = beta * (level - state.level) + (1.0 - beta) * state.trend;
+        state.seasonal[t % m] = gamma * (value - level) + (1.0 - gamma) * season;
+        state.level = level;
+    }
+    state
+}
+
+//This function picks the smoothing parameters with the smallest squared one-step-ahead error on a grid
+//The trend is kept slow, since a fast one extrapolates a single good or bad week for the whole horizon
+fn fit_holt_winters(values: &[f64]) -> (Smoothing, HoltWintersState) {
+    let alphas: Vec<f64> = (1..20).map(|i| i as f64 * 0.05).collect();
+    let betas = [0.0, 0.01, 0.02, 0.05, 0.1];
+    let gammas: Vec<f64> = (1..10).map(|i| i as f64 * 0.1).collect();
+    let mut best: Option<(f64, Smoothing)> = None;
+    for &alpha in &alphas {
+        for &beta in &betas {
+            for &gamma in &gammas {
+                let smoothing = Smoothing { alpha, beta, gamma };
+                let error: f64 = run_holt_winters(values, smoothing).errors.iter().map(|e| e * e).sum();
+                if best.is_none_or(|(lowest, _)| error < lowest) {
+                    best = Some((error, smoothing));
+                }
+            }
+        }
+    }
+    let smoothing = best.expect("the grid is not empty").1;
+    (smoothing, run_holt_winters(values, smoothing))
+}
+
+//This function forecasts the series h = 1..horizon days ahead, with the interval offsets of each day
+//The variance h days ahead grows by the sum of c_j² with c_j = alpha (1 + j beta) + gamma for every full week j
+fn holt_winters_ahead(values: &[f64], horizon: usize, coverage: f64) -> (Smoothing, f64, Vec<(f64, f64, f64)>) {
+    let (smoothing, state) = fit_holt_winters(values);
+    let residual_sd = standard_deviation(&state.errors);
+    let errors = sorted(state.errors.clone());
+    let n = values.len();
+    let mut growth = 1.0;
+    let ahead = (1..=horizon)
+        .map(|h| {
+            if h > 1 {
+                let j = (h - 1) as f64;
+                let weekly = if (h - 1) % SEASON_LENGTH == 0 { smoothing.gamma } else { 0.0 };
+                growth += (smoothing.alpha * (1.0 + j * smoothing.beta) + weekly).powi(2);
+            }
+            let mean = state.level + h as f64 * state.trend + state.seasonal[(n - 1 + h) % SEASON_LENGTH];
+            let (lower, upper) = interval(&errors, growth, coverage);
+            (mean, lower, upper)
+        })
+        .collect();
+    (smoothing, residual_sd, ahead)
+}
+
+//This function forecasts with Holt-Winters on the rentals themselves
+pub fn holt_winters(series: &DailySeries, horizon: usize, coverage: f64) -> Forecast {
+    let (smoothing, residual_sd, ahead) = holt_winters_ahead(&series.values, horizon, coverage);
+    let days = ahead
+        .into_iter()
+        .enumerate()
+        .map(|(i, (mean, lower, upper))| {
+            let (date, day_type) = future_day(series, i + 1);
+            ForecastDay { date, day_type, mean: mean.max(0.0), lower: (mean + lower).max(0.0), upper: (mean + upper).max(0.0) }
+        })
+        .collect();
+    Forecast { method: ForecastMethod::HoltWinters, days, residual_sd, smoothing: Some(smoothing) }
+}
+
+//This function reads the expected weather of the coming days from a CSV file, or a JSON array when the path ends in .json
+pub fn read_weather(path: &str) -> Result<Vec<DayWeather>, Box<dyn Error>> {
+    Ok(read_rows(path)?.into_iter().collect::<Result<_, _>>()?)
+}
+
+//This function describes a future day for the regression, from the given weather or else the average weather of its month
+fn future_query(date: &str, day_type: DayType, given: Option<&DayWeather>, days: &[DaySummary]) -> DayQuery {
+    let month = date.split('/').nth(1).and_then(|m| m.parse().ok()).unwrap_or(1);
+    if let Some(weather) = given {
+        return DayQuery { month, day_type, temperature_c: weather.temperature, rainfall_mm: weather.rainfall, snowfall_cm: weather.snowfall, humidity: weather.humidity };
+    }
+    let same_month: Vec<&DaySummary> = days.iter().filter(|day| day.month == month).collect();
+    let pool: Vec<&DaySummary> = if same_month.is_empty() { days.iter().collect() } else { same_month };
+    let average = |value: fn(&DaySummary) -> f64| pool.iter().map(|day| value(day)).sum::<f64>() / pool.len() as f64;
+    DayQuery {
+        month,
+        day_type,
+        temperature_c: average(|day| day.avg_temperature),
+        rainfall_mm: average(|day| day.total_rainfall),
+        snowfall_cm: average(|day| day.total_snowfall),
+        humidity: Some(average(|day| day.avg_humidity)),
+    }
+}
+
+//This function forecasts what the weather does not explain with Holt-Winters, then adds the regression estimate of each future day
+//The regression handles the months and the weather, so the weekly pattern and recent level are left for Holt-Winters
+pub fn weather_adjusted(days: &[DaySummary], series: &DailySeries, weather: &[DayWeather], horizon: usize, coverage: f64) -> Result<Forecast, String> {
+    let mut regression = RegressionPredictor::new(1.0);
+    regression.fit(days)?;
+    let mut leftover = vec![None; series.values.len()];
+    for (number, day) in numbered_days(days)? {
+        let expected = regression.predict(&DayQuery::of_day(day)).expect("fitted above");
+        leftover[(number - series.start) as usize] = Some(day.total_rentals as f64 - expected);
+    }
+    let (smoothing, residual_sd, ahead) = holt_winters_ahead(&fill_gaps(&leftover), horizon, coverage);
+    let by_date: HashMap<&str, &DayWeather> = weather.iter().map(|day| (day.date.trim(), day)).collect();
+    let days = ahead
+        .into_iter()
+        .enumerate()
+        .map(|(i, (rest, lower, upper))| {
+            let (date, day_type) = future_day(series, i + 1);
+            let query = future_query(&date, day_type, by_date.get(date.as_str()).copied(), days);
+            let mean = regression.predict(&query).expect("fitted above") + rest;
+            ForecastDay { date, day_type, mean: mean.max(0.0), lower: (mean + lower).max(0.0), upper: (mean + upper).max(0.0) }
+        })
+        .collect();
+    Ok(Forecast { method: ForecastMethod::WeatherAdjusted, days, residual_sd, smoothing: Some(smoothing) })
+}
+
+//This function prints the forecast as a table
+pub fn print_forecast(forecast: &Forecast, coverage: f64) {
+    print!("[{} forecast, one-step error sd {:.0}", forecast.method, forecast.residual_sd);
+    if let Some(Smoothing { alpha, beta, gamma }) = forecast.smoothing {
+        print!(", alpha {:.2} beta {:.2} gamma {:.2}", alpha, beta, gamma);
+    }
+    println!("]");
+    println!("  {:<12} {:<8} {:>8} {:>19}", "Date", "Day", "Rentals", format!("{:.0}% interval", coverage * 100.0));
+    for day in &forecast.days {
+        println!("  {:<12} {:<8} {:>8.0} {:>9.0} - {:<7.0}", day.date, day.day_type.to_string(), day.mean, day.lower, day.upper);
+    }
+}
+
+//This struct is one line of the saved forecasts
+#[derive(Serialize)]
+struct ForecastRow<'a> {
+    method: String,
+    date: &'a str,
+    day_type: String,
+    mean: f64,
+    lower: f64,
+    upper: f64,
+}
+
+//This function writes every forecast as CSV, or as JSON when the path ends in .json
+pub fn write_forecasts(path: &str, forecasts: &[Forecast]) -> Result<(), Box<dyn Error>> {
+    let rows: Vec<ForecastRow> = forecasts
+        .iter()
+        .flat_map(|forecast| {
+            forecast.days.iter().map(|day| ForecastRow {
+                method: forecast.method.to_string(),
+                date: &day.date,
+                day_type: day.day_type.to_string(),
+                mean: day.mean,
+                lower: day.lower,
+                upper: day.upper,
+            })
+        })
+        .collect();
+    write_rows(path, &rows)
+}
+
+//The tests check that a clean weekly pattern is forecast exactly and that gaps in the calendar are filled
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_day;
+
+    fn make_day(number: i64, total_rentals: i32) -> DaySummary {
+        let date = date_of(number);
+        DaySummary { day_type: day_type(&date, "No Holiday"), avg_temperature: 15.0 + (number % 5) as f64, ..test_day(&date, total_rentals) }
+    }
+
+    #[test]
+    fn test_weekly_forecasts() {
+        let start = day_number("04/06/2018").unwrap();
+        let pattern = [20000, 21000, 22000, 21000, 23000, 15000, 14000];
+        //the 10th day is missing, as if the bikes had not been running
+        let days: Vec<DaySummary> = (0..42).filter(|&i| i != 10).map(|i| make_day(start + i, pattern[i as usize % 7])).collect();
+        let series = daily_series(&days).unwrap();
+        assert_eq!((series.values.len(), series.filled), (42, 1));
+        assert_eq!(series.values[10], pattern[3] as f64);
+
+        let naive = seasonal_naive(&series, 8, 0.8);
+        assert_eq!(naive.days[0].date, "16/07/2018");
+        assert_eq!(naive.days[0].day_type, DayType::Weekday);
+        assert_eq!(naive.days[5].mean, 15000.0);
+        assert_eq!(naive.days[7].mean, 20000.0);
+        assert_eq!((naive.days[7].lower, naive.days[7].upper), (20000.0, 20000.0));
+
+        let smoothed = holt_winters(&series, 7, 0.8);
+        for (day, expected) in smoothed.days.iter().zip(pattern) {
+            assert!((day.mean - expected as f64).abs() < 1.0);
+        }
+        let adjusted = weather_adjusted(&days, &series, &[], 7, 0.8).unwrap();
+        assert!((adjusted.days[6].mean - 14000.0).abs() < 1000.0);
+        assert!(adjusted.days.iter().all(|day| day.lower <= day.mean && day.mean <= day.upper));
+    }
+}
@@ -0,0 +1,427 @@
+//This module looks at the structure of the similarity graphs: connected components, degrees and communities of similar days
+use crate::common::DaySummary;
+use crate::model::day_number;
+use crate::search::{Adjacency, WeightedGraph};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+//This struct describes one group of days, either a connected component or a community
+#[derive(Debug, Clone)]
+pub struct GroupSummary {
+    pub members: Vec<usize>,
+    pub mean_rentals: f64,
+    pub min_rentals: i32,
+    pub max_rentals: i32,
+    //the most common weather/temperature/precipitation combination and the share of days that have it
+    pub regime: String,
+    pub regime_share: f64,
+}
+
+//This function splits the days into connected components with bfs, largest component first
+pub fn connected_components<G: Adjacency>(graph: &G, node_count: usize) -> Vec<Vec<usize>> {
+    let mut visited = HashSet::new();
+    let mut components = Vec::new();
+    for source in 0..node_count {
+        if !visited.insert(source) {
+            continue;
+        }
+        let mut component = Vec::new();
+        let mut queue = VecDeque::from([source]);
+        while let Some(node) = queue.pop_front() {
+            component.push(node);
+            for neighbor in graph.neighbors(node) {
+                if visited.insert(neighbor) {
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        component.sort_unstable();
+        components.push(component);
+    }
+    //sort_by_key is stable so components of equal size stay in order of their first day
+    components.sort_by_key(|c| Reverse(c.len()));
+    components
+}
+
+//This function counts how many days have each number of neighbours
+pub fn degree_distribution<G: Adjacency>(graph: &G, node_count: usize) -> BTreeMap<usize, usize> {
+    let mut distribution = BTreeMap::new();
+    for node in 0..node_count {
+        *distribution.entry(graph.neighbors(node).count()).or_insert(0) += 1;
+    }
+    distribution
+}
+
+//This function finds communities by weighted label propagation
+//Every day starts in its own community and then, going through the days in order, joins the community its neighbours
+//are most strongly tied to (by edge similarity), until a full pass changes nothing or max_passes is reached
+//Ties go to the lowest label so the result does not depend on hash order
+pub fn label_propagation(graph: &WeightedGraph, node_count: usize, max_passes: usize) -> Vec<Vec<usize>> {
+    let mut labels: Vec<usize> = (0..node_count).collect();
+    for _ in 0..max_passes {
+        let mut changed = false;
+        for node in 0..node_count {
+            let mut strength: BTreeMap<usize, f64> = BTreeMap::new();
+            for edge in graph.adj_list.get(&node).into_iter().flatten() {
+                *strength.entry(labels[edge.node]).or_insert(0.0) += edge.similarity();
+            }
+            let best = strength
+                .into_iter()
+                .fold(None, |best: Option<(usize, f64)>, (label, total)| match best {
+                    Some((_, best_total)) if best_total >= total => best,
+                    _ => Some((label, total)),
+                });
+            if let Some((label, _)) = best {
+                if label != labels[node] {
+                    labels[node] = label;
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for (node, label) in labels.into_iter().enumerate() {
+        groups.entry(label).or_default().push(node);
+    }
+    let mut communities: Vec<Vec<usize>> = groups.into_values().collect();
+    communities.sort_by_key(|c| Reverse(c.len()));
+    communities
+}
+
+//This function measures how much more the edges stay inside the communities than they would by chance, using edge similarity as weight
+//Values near 0 mean no structure and values towards 1 mean clearly separated communities
+pub fn modularity(graph: &WeightedGraph, communities: &[Vec<usize>]) -> f64 {
+    let mut community_of = HashMap::new();
+    for (id, members) in communities.iter().enumerate() {
+        for &node in members {
+            community_of.insert(node, id);
+        }
+    }
+    let mut strength: HashMap<usize, f64> = HashMap::new();
+    let mut inside = vec![0.0; communities.len()];
+    let mut total = vec![0.0; communities.len()];
+    let mut twice_weight = 0.0;
+    for (&node, edges) in &graph.adj_list {
+        for edge in edges {
+            let weight = edge.similarity();
+            *strength.entry(node).or_insert(0.0) += weight;
+            twice_weight += weight;
+            if community_of.get(&node) == community_of.get(&edge.node) {
+                if let Some(&id) = community_of.get(&node) {
+                    inside[id] += weight;
+                }
+            }
+        }
+    }
+    if twice_weight == 0.0 {
+        return 0.0;
+    }
+    for (&node, s) in &strength {
+        if let Some(&id) = community_of.get(&node) {
+            total[id] += s;
+        }
+    }
+    inside
+        .iter()
+        .zip(&total)
+        .map(|(inside, total)| inside / twice_weight - (total / twice_weight).powi(2))
+        .sum()
+}
+
+//This function summarises the rentals and the most common weather regime of each group
+pub fn summarize_groups(groups: &[Vec<usize>], days: &[DaySummary]) -> Vec<GroupSummary> {
+    groups
+        .iter()
+        .filter(|members| !members.is_empty())
+        .map(|members| {
+            let rentals: Vec<i32> = members.iter().map(|&i| days[i].total_rentals).collect();
+            let mut regimes: BTreeMap<String, usize> = BTreeMap::new();
+            for &i in members {
+                let day = &days[i];
+                let key = format!("{}/{}/{}", day.weather, day.temperature_category, day.precipitation);
+                *regimes.entry(key).or_insert(0) += 1;
+            }
+            let (regime, count) = regimes
+                .into_iter()
+                .fold((String::new(), 0), |best, (key, count)| if count > best.1 { (key, count) } else { best });
+            GroupSummary {
+                members: members.clone(),
+                mean_rentals: rentals.iter().map(|&r| r as f64).sum::<f64>() / rentals.len() as f64,
+                min_rentals: *rentals.iter().min().unwrap_or(&0),
+                max_rentals: *rentals.iter().max().unwrap_or(&0),
+                regime,
+                regime_share: count as f64 / members.len() as f64,
+            }
+        })
+        .collect()
+}
+
+//This enum picks the centrality measure used to choose representative days
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Centrality {
+    Closeness,
+    Betweenness,
+    PageRank,
+}
+
+//This function gives the bfs hop distance from source to every node it can reach
+fn hop_distances<G: Adjacency>(graph: &G, node_count: usize, source: usize) -> Vec<Option<usize>> {
+    let mut distance = vec![None; node_count];
+    distance[source] = Some(0);
+    let mut queue = VecDeque::from([source]);
+    while let Some(node) = queue.pop_front() {
+        let next = distance[node].map(|d| d + 1);
+        for neighbor in graph.neighbors(node) {
+            if distance[neighbor].is_none() {
+                distance[neighbor] = next;
+                queue.push_back(neighbor);
+            }
+        }
+    }
+    distance
+}
+
+//This function scores every day by how close it is to the rest of its component, counting edges as steps
+//The score is scaled by the share of days the day can reach, so days in tiny components do not look central
+pub fn closeness_centrality<G: Adjacency>(graph: &G, node_count: usize) -> Vec<f64> {
+    (0..node_count)
+        .map(|source| {
+            let distances = hop_distances(graph, node_count, source);
+            let reached: Vec<usize> = distances.iter().flatten().copied().filter(|&d| d > 0).collect();
+            let total: usize = reached.iter().sum();
+            if total == 0 || node_count < 2 {
+                return 0.0;
+            }
+            let share = reached.len() as f64 / (node_count - 1) as f64;
+            share * reached.len() as f64 / total as f64
+        })
+        .collect()
+}
+
+//This function counts for every day on how many shortest paths between other days it lies, using Brandes' algorithm
+pub fn betweenness_centrality<G: Adjacency>(graph: &G, node_count: usize) -> Vec<f64> {
+    let mut centrality = vec![0.0; node_count];
+    for source in 0..node_count {
+        let mut stack = Vec::new();
+        let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); node_count];
+        let mut paths = vec![0.0; node_count];
+        let mut distance: Vec<Option<usize>> = vec![None; node_count];
+        paths[source] = 1.0;
+        distance[source] = Some(0);
+        let mut queue = VecDeque::from([source]);
+
+        while let Some(node) = queue.pop_front() {
+            stack.push(node);
+            let next = distance[node].map(|d| d + 1);
+            for neighbor in graph.neighbors(node) {
+                if distance[neighbor].is_none() {
+                    distance[neighbor] = next;
+                    queue.push_back(neighbor);
+                }
+                if distance[neighbor] == next {
+                    paths[neighbor] += paths[node];
+                    predecessors[neighbor].push(node);
+                }
+            }
+        }
+
+        let mut dependency = vec![0.0; node_count];
+        while let Some(node) = stack.pop() {
+            for &previous in &predecessors[node] {
+                dependency[previous] += paths[previous] / paths[node] * (1.0 + dependency[node]);
+            }
+            if node != source {
+                centrality[node] += dependency[node];
+            }
+        }
+    }
+    //every pair was counted from both ends
+    centrality.iter().map(|c| c / 2.0).collect()
+}
+
+//This function runs PageRank with the usual damping of 0.85, spreading the rank of days without edges over every day
+pub fn pagerank<G: Adjacency>(graph: &G, node_count: usize, iterations: usize) -> Vec<f64> {
+    if node_count == 0 {
+        return Vec::new();
+    }
+    let damping = 0.85;
+    let n = node_count as f64;
+    let degrees: Vec<usize> = (0..node_count).map(|node| graph.neighbors(node).count()).collect();
+    let mut rank = vec![1.0 / n; node_count];
+    for _ in 0..iterations {
+        let dangling: f64 = (0..node_count).filter(|&node| degrees[node] == 0).map(|node| rank[node]).sum();
+        let mut next = vec![(1.0 - damping) / n + damping * dangling / n; node_count];
+        for node in 0..node_count {
+            if degrees[node] == 0 {
+                continue;
+            }
+            let share = damping * rank[node] / degrees[node] as f64;
+            for neighbor in graph.neighbors(node) {
+                next[neighbor] += share;
+            }
+        }
+        rank = next;
+    }
+    rank
+}
+
+//This function scores every day with the chosen measure
+pub fn centrality<G: Adjacency>(graph: &G, node_count: usize, measure: Centrality) -> Vec<f64> {
+    match measure {
+        Centrality::Closeness => closeness_centrality(graph, node_count),
+        Centrality::Betweenness => betweenness_centrality(graph, node_count),
+        Centrality::PageRank => pagerank(graph, node_count, 50),
+    }
+}
+
+//This function picks the top days of every group by centrality score
+//Ties are common on the exact graph, where every day of a clique scores the same, so they go to the day whose measured
+//temperature and rainfall are closest to the group average, and then to the earliest date, never to the position in the list
+pub fn representative_days(groups: &[Vec<usize>], scores: &[f64], days: &[DaySummary], per_group: usize) -> Vec<Vec<usize>> {
+    groups
+        .iter()
+        .map(|members| {
+            let count = members.len().max(1) as f64;
+            let mean_temperature = members.iter().map(|&m| days[m].avg_temperature).sum::<f64>() / count;
+            let mean_rainfall = members.iter().map(|&m| days[m].total_rainfall).sum::<f64>() / count;
+            let off_centre = |m: usize| (days[m].avg_temperature - mean_temperature).powi(2) + (days[m].total_rainfall - mean_rainfall).powi(2);
+            let mut ranked = members.clone();
+            ranked.sort_by(|&a, &b| {
+                scores[b]
+                    .total_cmp(&scores[a])
+                    .then(off_centre(a).total_cmp(&off_centre(b)))
+                    .then(day_number(&days[a].date).cmp(&day_number(&days[b].date)))
+            });
+            ranked.truncate(per_group);
+            ranked
+        })
+        .collect()
+}
+
+//This function prints the representative days of each group with their rentals and weather next to the group average
+pub fn print_representatives(title: &str, groups: &[GroupSummary], representatives: &[Vec<usize>], scores: &[f64], days: &[DaySummary], limit: usize) {
+    println!("[{}]", title);
+    println!(
+        "  {:>4} {:>6} {:>12}  {:<10} {:>8} {:>7} {:>8} {:>10}  Conditions",
+        "#", "Days", "Group mean", "Date", "Rentals", "Temp", "Rain", "Centrality"
+    );
+    for (id, (group, picks)) in groups.iter().zip(representatives).take(limit).enumerate() {
+        for &i in picks {
+            let day = &days[i];
+            println!(
+                "  {:>4} {:>6} {:>12.0}  {:<10} {:>8} {:>6.1}C {:>6.1}mm {:>10.4}  {}/{}/{}",
+                id + 1,
+                group.members.len(),
+                group.mean_rentals,
+                day.date,
+                day.total_rentals,
+                day.avg_temperature,
+                day.total_rainfall,
+                scores[i],
+                day.weather,
+                day.temperature_category,
+                day.precipitation
+            );
+        }
+    }
+}
+
+//This function prints the groups as a table, leaving out the smallest ones when there are more than limit
+pub fn print_group_table(title: &str, groups: &[GroupSummary], limit: usize) {
+    println!("[{}: {} groups]", title, groups.len());
+    println!("  {:>4} {:>6} {:>12} {:>8} {:>8}  Main regime", "#", "Days", "Mean rentals", "Min", "Max");
+    for (id, group) in groups.iter().take(limit).enumerate() {
+        println!(
+            "  {:>4} {:>6} {:>12.0} {:>8} {:>8}  {} ({:.0}%)",
+            id + 1,
+            group.members.len(),
+            group.mean_rentals,
+            group.min_rentals,
+            group.max_rentals,
+            group.regime,
+            group.regime_share * 100.0
+        );
+    }
+    if groups.len() > limit {
+        let rest: usize = groups[limit..].iter().map(|g| g.members.len()).sum();
+        println!("  ... {} smaller groups with {} days in total", groups.len() - limit, rest);
+    }
+}
+
+//This function prints the degree distribution as a table of degree and number of days
+pub fn print_degree_table(distribution: &BTreeMap<usize, usize>) {
+    println!("[Degree distribution]");
+    println!("  {:>6} {:>6}", "Degree", "Days");
+    for (degree, count) in distribution {
+        println!("  {:>6} {:>6}", degree, count);
+    }
+}
+
+//The tests use two triangles joined by one weak edge, which should give one component but two communities
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_day;
+    use crate::model::date_of;
+    use crate::search::Edge;
+
+    fn two_triangles() -> WeightedGraph {
+        let mut adj_list: HashMap<usize, Vec<Edge>> = HashMap::new();
+        for (a, b, distance) in [(0, 1, 0.0), (1, 2, 0.0), (0, 2, 0.0), (3, 4, 0.0), (4, 5, 0.0), (3, 5, 0.0), (2, 3, 9.0)] {
+            adj_list.entry(a).or_default().push(Edge { node: b, distance });
+            adj_list.entry(b).or_default().push(Edge { node: a, distance });
+        }
+        WeightedGraph { adj_list }
+    }
+
+    #[test]
+    fn test_components_and_degrees() {
+        let graph = two_triangles();
+        let components = connected_components(&graph, 7);
+        assert_eq!(components, vec![vec![0, 1, 2, 3, 4, 5], vec![6]]);
+
+        let degrees = degree_distribution(&graph, 7);
+        assert_eq!(degrees.get(&0), Some(&1));
+        assert_eq!(degrees.get(&2), Some(&4));
+        assert_eq!(degrees.get(&3), Some(&2));
+    }
+
+    #[test]
+    fn test_centrality_picks_bridge_days() {
+        let graph = two_triangles();
+        let betweenness = betweenness_centrality(&graph, 6);
+        //2 lies on all 6 shortest paths from 0 and 1 to the other triangle
+        assert_eq!(betweenness[2], 6.0);
+        assert_eq!(betweenness[0], 0.0);
+
+        let closeness = closeness_centrality(&graph, 6);
+        assert!(closeness[2] > closeness[0]);
+
+        let rank = pagerank(&graph, 6, 100);
+        assert!((rank.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!(rank[2] > rank[0]);
+
+        //Later positions hold earlier dates, and 5 is closer than 4 to the measured average of its triangle
+        let days: Vec<DaySummary> = [20.0, 20.0, 20.0, 20.0, 26.0, 22.0]
+            .iter()
+            .enumerate()
+            .map(|(i, &avg_temperature)| DaySummary { avg_temperature, ..test_day(&date_of(17600 - i as i64), 1000) })
+            .collect();
+        let groups = vec![vec![0, 1, 2], vec![3, 4, 5]];
+        assert_eq!(representative_days(&groups, &betweenness, &days, 1), vec![vec![2], vec![3]]);
+        assert_eq!(representative_days(&groups, &betweenness, &days, 3), vec![vec![2, 1, 0], vec![3, 5, 4]]);
+    }
+
+    #[test]
+    fn test_label_propagation_splits_weak_edge() {
+        let graph = two_triangles();
+        let communities = label_propagation(&graph, 6, 20);
+        assert_eq!(communities, vec![vec![0, 1, 2], vec![3, 4, 5]]);
+        assert!(modularity(&graph, &communities) > 0.3);
+        assert!(modularity(&graph, &[vec![0, 1, 2, 3, 4, 5]]).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,205 @@
+//This module predicts the rentals of each of the 24 hours of a day from an hourly weather forecast
+//It either averages the most similar past hours, scored like the day similarity, or evaluates the boosted trees hour by hour
+use crate::boosting::{day_type_code, hour_features, BoostedModel, DAY_TYPE, HOURLY_FEATURES, DEW_POINT, HOUR, HUMIDITY, MONTH, RAINFALL, SNOWFALL, SOLAR_RADIATION, TEMPERATURE, VISIBILITY, WIND_SPEED};
+use crate::common::{DayType, RawRecord};
+use crate::table::read_rows;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+//This struct is the forecast for one hour; the values a forecast rarely has may be left out
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct HourForecast {
+    pub hour: usize,
+    pub temperature: f64,
+    pub humidity: f64,
+    #[serde(default)]
+    pub wind_speed: f64,
+    #[serde(default)]
+    pub rainfall: f64,
+    #[serde(default)]
+    pub snowfall: f64,
+    #[serde(default)]
+    pub visibility: Option<f64>,
+    #[serde(default)]
+    pub solar_radiation: Option<f64>,
+    #[serde(default)]
+    pub dew_point_temp: Option<f64>,
+}
+
+//This struct is the day to predict: when it is, and its forecast for every hour from 0 to 23
+#[derive(Debug, Clone)]
+pub struct HourlyQuery {
+    pub month: u32,
+    pub day_type: DayType,
+    pub hours: Vec<HourForecast>,
+}
+
+//This struct is one line of the output
+#[derive(Debug, Clone, Serialize)]
+pub struct HourPrediction {
+    pub hour: usize,
+    pub rentals: f64,
+}
+
+//This struct sets how much each difference between two hours adds to their score, like SimilarityConfig does for days
+//Each weight is reached when the difference reaches its range
+#[derive(Debug, Clone, Copy)]
+pub struct HourSimilarity {
+    pub month: f64,
+    pub day_type: f64,
+    pub temperature: f64,
+    pub humidity: f64,
+    pub wind_speed: f64,
+    pub rainfall: f64,
+    pub snowfall: f64,
+}
+
+impl Default for HourSimilarity {
+    fn default() -> Self {
+        HourSimilarity { month: 10.0, day_type: 30.0, temperature: 20.0, humidity: 10.0, wind_speed: 5.0, rainfall: 20.0, snowfall: 15.0 }
+    }
+}
+
+//Ranges of the measured differences: 20°C, 100% humidity, 8 m/s wind, 5 mm rain and 3 cm snow in an hour
+const RANGES: [(usize, f64); 5] = [(TEMPERATURE, 20.0), (HUMIDITY, 100.0), (WIND_SPEED, 8.0), (RAINFALL, 5.0), (SNOWFALL, 3.0)];
+
+impl HourSimilarity {
+    //This function scores how different two hours are, 0 being the same; both rows follow HOURLY_FEATURES
+    pub fn score(&self, a: &[f64], b: &[f64]) -> f64 {
+        let months_apart = (a[MONTH] - b[MONTH]).abs();
+        let month = months_apart.min(12.0 - months_apart) / 6.0;
+        let day_type = if a[DAY_TYPE] == b[DAY_TYPE] { 0.0 } else { 1.0 };
+        let weights = [self.temperature, self.humidity, self.wind_speed, self.rainfall, self.snowfall];
+        let measured: f64 = RANGES.iter().zip(weights).map(|(&(j, range), weight)| weight * ((a[j] - b[j]).abs() / range).min(1.0)).sum();
+        self.month * month + self.day_type * day_type + measured
+    }
+}
+
+//This function reads the forecast from a CSV file, or a JSON array when the path ends in .json, and checks every hour is there once
+pub fn read_forecast(path: &str) -> Result<Vec<HourForecast>, Box<dyn Error>> {
+    let mut hours: Vec<HourForecast> = read_rows(path)?.into_iter().collect::<Result<_, _>>()?;
+    hours.sort_by_key(|h| h.hour);
+    let listed: Vec<usize> = hours.iter().map(|h| h.hour).collect();
+    if listed != (0..24).collect::<Vec<usize>>() {
+        return Err(format!("{} has to list every hour from 0 to 23 once, it lists {:?}", path, listed).into());
+    }
+    Ok(hours)
+}
+
+//This function builds the feature row of one forecast hour, taking what the forecast leaves out from the typical hour
+fn forecast_row(query: &HourlyQuery, forecast: &HourForecast, typical: Option<&[f64]>) -> Vec<f64> {
+    let typical_value = |j: usize| typical.map_or(0.0, |row| row[j]);
+    let mut row = vec![0.0; HOURLY_FEATURES.len()];
+    row[HOUR] = forecast.hour as f64;
+    row[MONTH] = query.month as f64;
+    row[DAY_TYPE] = day_type_code(query.day_type);
+    row[TEMPERATURE] = forecast.temperature;
+    row[HUMIDITY] = forecast.humidity;
+    row[WIND_SPEED] = forecast.wind_speed;
+    row[VISIBILITY] = forecast.visibility.unwrap_or(typical_value(VISIBILITY));
+    //the dew point follows the temperature, so without a forecast it is shifted by how far the temperature is from the typical hour
+    row[DEW_POINT] = forecast.dew_point_temp.unwrap_or(typical_value(DEW_POINT) + forecast.temperature - typical_value(TEMPERATURE));
+    row[SOLAR_RADIATION] = forecast.solar_radiation.unwrap_or(typical_value(SOLAR_RADIATION));
+    row[RAINFALL] = forecast.rainfall;
+    row[SNOWFALL] = forecast.snowfall;
+    row
+}
+
+//This struct keeps the past hours split by hour of the day, since 8am and 3am are never alike
+pub struct HourIndex {
+    by_hour: Vec<Vec<(Vec<f64>, f64)>>,
+}
+
+impl HourIndex {
+    pub fn new(records: &[RawRecord]) -> HourIndex {
+        let mut by_hour = vec![Vec::new(); 24];
+        for record in records {
+            by_hour[(record.hour as usize).min(23)].push((hour_features(record), record.rented_bike_count as f64));
+        }
+        HourIndex { by_hour }
+    }
+}
+
+//This function predicts each hour as the weighted average of the k most similar past hours at the same time of day
+//Closer hours count more, with the weight 1 / (1 + score) that the day prediction uses
+pub fn nearest_hours(index: &HourIndex, query: &HourlyQuery, similarity: &HourSimilarity, k: usize) -> Vec<HourPrediction> {
+    query
+        .hours
+        .iter()
+        .map(|forecast| {
+            let row = forecast_row(query, forecast, None);
+            let mut scored: Vec<(f64, f64)> = index.by_hour[forecast.hour.min(23)].iter().map(|(past, rentals)| (similarity.score(&row, past), *rentals)).collect();
+            scored.sort_by(|a, b| a.0.total_cmp(&b.0));
+            scored.truncate(k);
+            let total_weight: f64 = scored.iter().map(|(score, _)| 1.0 / (1.0 + score)).sum();
+            let rentals = if total_weight > 0.0 { scored.iter().map(|(score, rentals)| rentals / (1.0 + score)).sum::<f64>() / total_weight } else { 0.0 };
+            HourPrediction { hour: forecast.hour, rentals }
+        })
+        .collect()
+}
+
+//This function predicts each hour with the boosted trees
+pub fn boosted_hours(model: &BoostedModel, query: &HourlyQuery) -> Vec<HourPrediction> {
+    query
+        .hours
+        .iter()
+        .map(|forecast| {
+            let typical = model.typical_hour(query.month, forecast.hour);
+            HourPrediction { hour: forecast.hour, rentals: model.predict(&forecast_row(query, forecast, Some(&typical))).max(0.0) }
+        })
+        .collect()
+}
+
+//The test checks that every hour is predicted from the past hours with the closest weather
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(hour: u32, temperature: f64, rented_bike_count: i32) -> RawRecord {
+        RawRecord {
+            date: "04/06/2018".to_string(),
+            rented_bike_count,
+            hour,
+            temperature,
+            humidity: 50.0,
+            wind_speed: 1.0,
+            visibility: 2000.0,
+            dew_point_temp: 10.0,
+            solar_radiation: 0.0,
+            rainfall: 0.0,
+            snowfall: 0.0,
+            seasons: "Summer".to_string(),
+            holiday: "No Holiday".to_string(),
+            functioning_day: "Yes".to_string(),
+            month: 6,
+        }
+    }
+
+    #[test]
+    fn test_nearest_hours() {
+        let mut records = Vec::new();
+        for hour in 0..24 {
+            records.push(record(hour, 25.0, 1000 + hour as i32));
+            records.push(record(hour, 0.0, 100));
+        }
+        let forecast = |hour: usize| HourForecast {
+            hour,
+            temperature: 24.0,
+            humidity: 50.0,
+            wind_speed: 1.0,
+            rainfall: 0.0,
+            snowfall: 0.0,
+            visibility: None,
+            solar_radiation: None,
+            dew_point_temp: None,
+        };
+        let query = HourlyQuery { month: 6, day_type: DayType::Weekday, hours: (0..24).map(forecast).collect() };
+        let predictions = nearest_hours(&HourIndex::new(&records), &query, &HourSimilarity::default(), 1);
+        assert_eq!(predictions.len(), 24);
+        assert_eq!(predictions[8].hour, 8);
+        assert_eq!(predictions[8].rentals, 1008.0);
+        //04/06/2018 was a Monday, so a weekend forecast is further from every past hour but still closest to the warm ones
+        let weekend = HourlyQuery { day_type: DayType::Weekend, ..query };
+        assert_eq!(nearest_hours(&HourIndex::new(&records), &weekend, &HourSimilarity::default(), 1)[3].rentals, 1003.0);
+    }
+}
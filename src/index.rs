@@ -0,0 +1,445 @@
+//This module saves the hourly records, the daily summaries and the day graph to a binary file so later runs do not have to reparse the CSV
+//The file starts with a version number and a stamp of the CSV it was built from, and is rebuilt when either does not match
+use crate::common::{DaySummary, DayType, PrecipitationIntensity, RawRecord, TemperatureCategory, Weather};
+use crate::data::load_data;
+use crate::model::summarize_daily_totals;
+use crate::search::{build_graph, Graph};
+use std::error::Error;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MAGIC: &[u8; 8] = b"BIKEIDX\0";
+//Bump this whenever the layout below, RawRecord or DaySummary changes, so old files are rebuilt instead of misread
+pub const INDEX_VERSION: u32 = 5;
+//A CSV changed this recently could change again within the resolution of its modification time, so its stamp is not trusted
+const RACY_SECONDS: u64 = 2;
+
+//The hourly records, the days and the graph stored in an index
+pub type IndexContents = (Vec<RawRecord>, Vec<DaySummary>, Graph);
+
+//This struct identifies the CSV an index was built from
+//The size and modification time are compared first, and the whole file is only hashed when they differ
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SourceStamp {
+    pub size: u64,
+    //nanoseconds since 1970, or 0 when the file was too fresh to be trusted
+    pub modified: u64,
+    pub checksum: u64,
+}
+
+//This enum tells the caller whether the index was reused or why it had to be rebuilt
+#[derive(Debug, Clone, PartialEq)]
+pub enum IndexStatus {
+    Loaded,
+    Built(String),
+}
+
+//This function computes the 64-bit FNV-1a hash of the source file, which changes whenever any byte of the CSV changes
+pub fn source_checksum(path: &str) -> Result<u64, Box<dyn Error>> {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in fs::read(path)? {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    Ok(hash)
+}
+
+//This function reads the size and modification time of the source, leaving the modification time at 0 for a file
+//that changed in the last few seconds, so the next run hashes it instead of trusting a time that may not have moved
+fn file_stamp(path: &str) -> Result<(u64, u64), Box<dyn Error>> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata.modified()?;
+    let fresh = SystemTime::now().duration_since(modified).map_or(true, |age| age.as_secs() < RACY_SECONDS);
+    let nanos = if fresh { 0 } else { modified.duration_since(UNIX_EPOCH).map_or(0, |time| time.as_nanos() as u64) };
+    Ok((metadata.len(), nanos))
+}
+
+fn weather_code(weather: &Weather) -> u8 {
+    match weather {
+        Weather::Sunny => 0,
+        Weather::Rainy => 1,
+        Weather::Snowy => 2,
+        Weather::Foggy => 3,
+    }
+}
+
+fn temperature_code(temperature: &TemperatureCategory) -> u8 {
+    match temperature {
+        TemperatureCategory::Hot => 0,
+        TemperatureCategory::Moderate => 1,
+        TemperatureCategory::Cold => 2,
+    }
+}
+
+fn precipitation_code(precipitation: &PrecipitationIntensity) -> u8 {
+    match precipitation {
+        PrecipitationIntensity::Light => 0,
+        PrecipitationIntensity::Heavy => 1,
+        PrecipitationIntensity::None => 2,
+    }
+}
+
+fn day_type_code(day_type: DayType) -> u8 {
+    match day_type {
+        DayType::Weekday => 0,
+        DayType::Weekend => 1,
+        DayType::Holiday => 2,
+    }
+}
+
+fn push_text(out: &mut Vec<u8>, text: &str) {
+    out.extend_from_slice(&(text.len() as u16).to_le_bytes());
+    out.extend_from_slice(text.as_bytes());
+}
+
+//This function encodes the index: header, then every hourly record, every day and the classes of the day graph, all little-endian
+pub fn encode_index(stamp: &SourceStamp, records: &[RawRecord], days: &[DaySummary], graph: &Graph) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&INDEX_VERSION.to_le_bytes());
+    out.extend_from_slice(&stamp.size.to_le_bytes());
+    out.extend_from_slice(&stamp.modified.to_le_bytes());
+    out.extend_from_slice(&stamp.checksum.to_le_bytes());
+
+    out.extend_from_slice(&(records.len() as u32).to_le_bytes());
+    for record in records {
+        push_text(&mut out, &record.date);
+        out.extend_from_slice(&record.rented_bike_count.to_le_bytes());
+        out.extend_from_slice(&record.hour.to_le_bytes());
+        for value in [
+            record.temperature,
+            record.humidity,
+            record.wind_speed,
+            record.visibility,
+            record.dew_point_temp,
+            record.solar_radiation,
+            record.rainfall,
+            record.snowfall,
+        ] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        push_text(&mut out, &record.seasons);
+        push_text(&mut out, &record.holiday);
+        push_text(&mut out, &record.functioning_day);
+        out.extend_from_slice(&record.month.to_le_bytes());
+    }
+
+    out.extend_from_slice(&(days.len() as u32).to_le_bytes());
+    for day in days {
+        push_text(&mut out, &day.date);
+        out.extend_from_slice(&day.month.to_le_bytes());
+        out.extend_from_slice(&day.total_rentals.to_le_bytes());
+        out.push(weather_code(&day.weather));
+        out.push(temperature_code(&day.temperature_category));
+        out.push(precipitation_code(&day.precipitation));
+        out.extend_from_slice(&day.avg_temperature.to_le_bytes());
+        out.extend_from_slice(&day.total_rainfall.to_le_bytes());
+        out.extend_from_slice(&day.total_snowfall.to_le_bytes());
+        out.extend_from_slice(&day.avg_humidity.to_le_bytes());
+        out.push(day_type_code(day.day_type));
+    }
+
+    out.extend_from_slice(&(graph.classes.len() as u32).to_le_bytes());
+    for class in &graph.classes {
+        out.extend_from_slice(&(class.len() as u32).to_le_bytes());
+        for &node in class {
+            out.extend_from_slice(&(node as u32).to_le_bytes());
+        }
+    }
+    out
+}
+
+//This struct reads the encoded values back in order and fails instead of panicking when the file is cut short
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], Box<dyn Error>> {
+        let end = self.position.checked_add(count).filter(|&end| end <= self.bytes.len()).ok_or("index file is truncated")?;
+        let slice = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Box<dyn Error>> {
+        Ok(self.take(N)?.try_into()?)
+    }
+
+    fn u8(&mut self) -> Result<u8, Box<dyn Error>> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Box<dyn Error>> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, Box<dyn Error>> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, Box<dyn Error>> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn f64(&mut self) -> Result<f64, Box<dyn Error>> {
+        Ok(f64::from_le_bytes(self.array()?))
+    }
+
+    //This function reads how many entries follow, and refuses a count that could not fit in the rest of the file
+    //so a damaged count fails here instead of reserving gigabytes up front
+    fn count(&mut self, smallest_entry: usize) -> Result<usize, Box<dyn Error>> {
+        let count = self.u32()? as usize;
+        if count.saturating_mul(smallest_entry) > self.bytes.len() - self.position {
+            return Err("index file is truncated".into());
+        }
+        Ok(count)
+    }
+
+    fn text(&mut self) -> Result<String, Box<dyn Error>> {
+        let length = self.u16()? as usize;
+        Ok(String::from_utf8(self.take(length)?.to_vec())?)
+    }
+}
+
+//This function decodes an index together with the stamp of the source it was built from
+//It returns Ok(None) for a file from another version, and Err for a damaged file
+pub fn decode_index(bytes: &[u8]) -> Result<Option<(SourceStamp, IndexContents)>, Box<dyn Error>> {
+    let mut reader = Reader { bytes, position: 0 };
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err("not an index file".into());
+    }
+    if reader.u32()? != INDEX_VERSION {
+        return Ok(None);
+    }
+    let stamp = SourceStamp { size: reader.u64()?, modified: reader.u64()?, checksum: reader.u64()? };
+
+    //a record is at least its four empty texts, the rental count and hour, eight floats and the month
+    let record_count = reader.count(4 * 2 + 4 * 2 + 8 * 8 + 4)?;
+    let mut records = Vec::with_capacity(record_count);
+    for _ in 0..record_count {
+        records.push(RawRecord {
+            date: reader.text()?,
+            rented_bike_count: i32::from_le_bytes(reader.array()?),
+            hour: reader.u32()?,
+            temperature: reader.f64()?,
+            humidity: reader.f64()?,
+            wind_speed: reader.f64()?,
+            visibility: reader.f64()?,
+            dew_point_temp: reader.f64()?,
+            solar_radiation: reader.f64()?,
+            rainfall: reader.f64()?,
+            snowfall: reader.f64()?,
+            seasons: reader.text()?,
+            holiday: reader.text()?,
+            functioning_day: reader.text()?,
+            month: reader.u32()?,
+        });
+    }
+
+    //a day is at least an empty date, the month and rentals, four codes and four floats
+    let day_count = reader.count(2 + 4 * 2 + 4 + 8 * 4)?;
+    let mut days = Vec::with_capacity(day_count);
+    for _ in 0..day_count {
+        let date = reader.text()?;
+        let month = reader.u32()?;
+        let total_rentals = i32::from_le_bytes(reader.array()?);
+        let weather = match reader.u8()? {
+            0 => Weather::Sunny,
+            1 => Weather::Rainy,
+            2 => Weather::Snowy,
+            3 => Weather::Foggy,
+            _ => return Err("unknown weather code".into()),
+        };
+        let temperature_category = match reader.u8()? {
+            0 => TemperatureCategory::Hot,
+            1 => TemperatureCategory::Moderate,
+            2 => TemperatureCategory::Cold,
+            _ => return Err("unknown temperature code".into()),
+        };
+        let precipitation = match reader.u8()? {
+            0 => PrecipitationIntensity::Light,
+            1 => PrecipitationIntensity::Heavy,
+            2 => PrecipitationIntensity::None,
+            _ => return Err("unknown precipitation code".into()),
+        };
+        let avg_temperature = reader.f64()?;
+        let total_rainfall = reader.f64()?;
+        let total_snowfall = reader.f64()?;
+        let avg_humidity = reader.f64()?;
+        let day_type = match reader.u8()? {
+            0 => DayType::Weekday,
+            1 => DayType::Weekend,
+            2 => DayType::Holiday,
+            _ => return Err("unknown day type code".into()),
+        };
+        days.push(DaySummary { date, month, total_rentals, weather, temperature_category, precipitation, avg_temperature, total_rainfall, total_snowfall, avg_humidity, day_type });
+    }
+
+    let class_count = reader.count(4)?;
+    let mut classes = Vec::with_capacity(class_count);
+    let mut class_of = vec![usize::MAX; day_count];
+    for id in 0..class_count {
+        let size = reader.count(4)?;
+        let mut class = Vec::with_capacity(size);
+        for _ in 0..size {
+            let node = reader.u32()? as usize;
+            *class_of.get_mut(node).ok_or("graph refers to a missing day")? = id;
+            class.push(node);
+        }
+        classes.push(class);
+    }
+    if class_of.contains(&usize::MAX) || reader.position != bytes.len() {
+        return Err("index file is inconsistent".into());
+    }
+    Ok(Some((stamp, (records, days, Graph { classes, class_of }))))
+}
+
+//Writing to a temporary file first means an interrupted run never leaves half an index behind
+fn write_index(index_path: &str, stamp: &SourceStamp, (records, days, graph): &IndexContents) -> Result<(), Box<dyn Error>> {
+    let temporary = format!("{}.tmp", index_path);
+    fs::write(&temporary, encode_index(stamp, records, days, graph))?;
+    fs::rename(&temporary, index_path)?;
+    Ok(())
+}
+
+//This function returns the hourly records, summaries and graph for the CSV, reading them from the index when it is up to date
+//and otherwise building them from the CSV and writing a fresh index for the next run
+//An index whose size and modification time match is used without reading the CSV at all. When only the time moved,
+//the checksum decides, and a matching index is rewritten with the new time so the next run can skip the hash again
+pub fn load_or_build(csv_path: &str, index_path: &str) -> Result<(IndexContents, IndexStatus), Box<dyn Error>> {
+    let (size, modified) = file_stamp(csv_path)?;
+    let mut checksum = None;
+    let reason = match fs::read(index_path) {
+        Ok(bytes) => match decode_index(&bytes) {
+            Ok(Some((stamp, contents))) if stamp.size == size && stamp.modified == modified && modified != 0 => {
+                return Ok((contents, IndexStatus::Loaded));
+            }
+            Ok(Some((stamp, contents))) => {
+                let current = source_checksum(csv_path)?;
+                checksum = Some(current);
+                if stamp.size == size && stamp.checksum == current {
+                    if stamp.modified != modified {
+                        write_index(index_path, &SourceStamp { size, modified, checksum: current }, &contents)?;
+                    }
+                    return Ok((contents, IndexStatus::Loaded));
+                }
+                "the data changed".to_string()
+            }
+            Ok(None) => "the index version changed".to_string(),
+            Err(e) => format!("the index could not be read ({})", e),
+        },
+        Err(_) => "there was no index yet".to_string(),
+    };
+
+    let checksum = match checksum {
+        Some(checksum) => checksum,
+        None => source_checksum(csv_path)?,
+    };
+    let records = load_data(csv_path)?;
+    let days = summarize_daily_totals(&records);
+    let graph = build_graph(&days);
+    let contents = (records, days, graph);
+    write_index(index_path, &SourceStamp { size, modified, checksum }, &contents)?;
+    Ok((contents, IndexStatus::Built(reason)))
+}
+
+//The tests round-trip an index and check that a changed source or a damaged file is not trusted
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_day;
+
+    #[test]
+    fn test_index_round_trip() {
+        let days = vec![
+            DaySummary {
+                weather: Weather::Snowy,
+                temperature_category: TemperatureCategory::Cold,
+                precipitation: PrecipitationIntensity::Heavy,
+                avg_temperature: -2.45,
+                ..test_day("01/12/2017", 9539)
+            },
+            DaySummary { avg_temperature: 10.5, total_rainfall: 1.5, day_type: DayType::Holiday, ..test_day("02/12/2017", 8523) },
+        ];
+        let records = vec![RawRecord {
+            date: "01/12/2017".to_string(),
+            rented_bike_count: 254,
+            hour: 0,
+            temperature: -2.1,
+            humidity: 81.0,
+            wind_speed: 1.5,
+            visibility: 2000.0,
+            dew_point_temp: 0.9,
+            solar_radiation: 0.0,
+            rainfall: 0.0,
+            snowfall: 0.5,
+            seasons: "Winter".to_string(),
+            holiday: "No Holiday".to_string(),
+            functioning_day: "Yes".to_string(),
+            month: 12,
+        }];
+        let graph = build_graph(&days);
+        let stamp = SourceStamp { size: 100, modified: 5, checksum: 7 };
+        let bytes = encode_index(&stamp, &records, &days, &graph);
+
+        let (loaded_stamp, (loaded_records, loaded, loaded_graph)) = decode_index(&bytes).unwrap().unwrap();
+        assert_eq!(loaded_stamp, stamp);
+        assert_eq!(loaded_records.len(), 1);
+        assert_eq!(loaded_records[0].snowfall, 0.5);
+        assert_eq!(loaded_records[0].holiday, "No Holiday");
+        assert_eq!(loaded_records[0].month, 12);
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].date, "01/12/2017");
+        assert_eq!(loaded[0].precipitation, PrecipitationIntensity::Heavy);
+        assert_eq!(loaded[1].avg_temperature, 10.5);
+        assert_eq!(loaded[1].day_type, DayType::Holiday);
+        assert_eq!(loaded_graph.classes, graph.classes);
+        assert_eq!(loaded_graph.class_of, graph.class_of);
+
+        let mut old_version = bytes.clone();
+        old_version[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&(INDEX_VERSION - 1).to_le_bytes());
+        assert!(decode_index(&old_version).unwrap().is_none());
+        assert!(decode_index(&bytes[..bytes.len() - 1]).is_err());
+
+        //a count far beyond the file is refused before anything is reserved for it
+        let count_at = MAGIC.len() + 4 + 8 * 3;
+        let mut inflated = bytes.clone();
+        inflated[count_at..count_at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(decode_index(&inflated).unwrap_err().to_string(), "index file is truncated");
+    }
+
+    #[test]
+    fn test_load_or_build_invalidates_on_change() {
+        let csv_path = "test_index_source.csv";
+        let index_path = "test_index_source.idx";
+        let header = "Date,Rented Bike Count,Hour,Temperature (°C),Humidity (%),Wind speed (m/s),Visibility (10m),Dew point temperature(°C),Solar Radiation (MJ/m2),Rainfall(mm),Snowfall (cm),Seasons,Holiday,Functioning Day";
+        fs::write(csv_path, format!("{}\n01/12/2017,254,0,2.1,81.0,1.5,2000,0.9,0.0,0.0,0.0,Winter,No Holiday,Yes\n", header)).unwrap();
+
+        let (_, first) = load_or_build(csv_path, index_path).unwrap();
+        assert!(matches!(first, IndexStatus::Built(_)));
+        let ((records, days, _), second) = load_or_build(csv_path, index_path).unwrap();
+        assert_eq!(second, IndexStatus::Loaded);
+        assert_eq!(days[0].total_rentals, 254);
+        assert_eq!(records[0].wind_speed, 1.5);
+
+        //The file was just written, so its time is not trusted yet and a same-size edit is still caught by the checksum
+        fs::write(csv_path, format!("{}\n01/12/2017,300,0,2.1,81.0,1.5,2000,0.9,0.0,0.0,0.0,Winter,No Holiday,Yes\n", header)).unwrap();
+        let ((_, days, _), third) = load_or_build(csv_path, index_path).unwrap();
+        assert!(matches!(third, IndexStatus::Built(_)));
+        assert_eq!(days[0].total_rentals, 300);
+
+        //Once the file is old enough the checksum is recorded with its time, and later runs trust the stamp
+        let an_hour_ago = SystemTime::now() - std::time::Duration::from_secs(3600);
+        fs::File::options().write(true).open(csv_path).unwrap().set_modified(an_hour_ago).unwrap();
+        let (_, fourth) = load_or_build(csv_path, index_path).unwrap();
+        assert_eq!(fourth, IndexStatus::Loaded);
+        let (stamp, _) = decode_index(&fs::read(index_path).unwrap()).unwrap().unwrap();
+        assert_ne!(stamp.modified, 0);
+        assert_eq!(file_stamp(csv_path).unwrap(), (stamp.size, stamp.modified));
+
+        fs::remove_file(csv_path).unwrap();
+        fs::remove_file(index_path).unwrap();
+    }
+}
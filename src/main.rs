@@ -1,195 +1,470 @@
 //This module makes use of modules created and displays some key statistics regarding bike rentals.
-//This module also takes in user input to conduct BFS and DFS to estimate the total bike rentals for given weather conditions.
+//It reads a subcommand from the command line (see cli::USAGE) and runs the matching search, prediction, export or analysis.
 
 //all the modules and the specific structs, enums, and functions are imported
 mod data;
 mod model;
 mod search;
 mod common;
+mod similarity;
+mod tuning;
+mod graph_analysis;
+mod export;
+mod table;
+mod index;
+mod batch;
+mod cli;
+mod prompt;
+mod stats;
+mod analysis;
+mod significance;
+mod predictor;
+mod boosting;
+mod hourly;
+mod forecast;
+mod evaluation;
 
-use std::io::{self, Write};
+use std::error::Error;
 use std::process;
-use std::collections::HashMap;
 
-use common::{Weather, TemperatureCategory, PrecipitationIntensity, Conditions};
+use common::{Conditions, DaySummary, DayType, RawRecord};
 use model::summarize_daily_totals;
-use search::{build_graph, bfs_closest, dfs_closest};
-
-//This function takes in the user input and according to it, intializes the condition used for search algorithms
-//Will skip explanations for other user inputs as they all essentially use the same code
-fn user_input() -> Conditions {
-    //takes in input as String
-    let mut input = String::new();
-
-    print!("Enter month (ex. 1-12): ");
-    io::stdout().flush().unwrap();
-    io::stdin().read_line(&mut input).unwrap();
-    let month: u32 = match input.trim().parse() {
-        Ok(m) if m >= 1 && m <= 12 => m,
-        _ => {
-            println!("Invalid");
-            0
-        }
-    };
+use search::{build_graph, build_weighted_graph, bfs_closest, dfs_closest, predict, astar_path, dijkstra_path, degrees_of_separation, find_day, Adjacency, EdgeRule, Graph};
+use similarity::save_config;
+use tuning::{fit_weights, FitOptions};
+use export::{exact_edges, export_graph, weighted_edges};
+use table::write_rows;
+use index::{load_or_build, IndexContents, IndexStatus};
+use batch::{read_conditions, run_batch};
+use graph_analysis::{centrality, connected_components, degree_distribution, label_propagation, modularity, print_degree_table, print_group_table, print_representatives, representative_days, summarize_groups};
+use cli::{parse_args, Command, GraphAction, ModelChoice, Options, USAGE};
+use prompt::{starting_conditions, Prompt};
+use analysis::{analyze, print_analysis, write_analysis};
+use boosting::{load_model, save_model, BoostedPredictor, BoostingConfig, HOURLY_FEATURES};
+use evaluation::{evaluate, print_leaderboard, Candidate, SearchSettings};
+use forecast::{daily_series, holt_winters, print_forecast, read_weather, seasonal_naive, weather_adjusted, write_forecasts, ForecastMethod};
+use hourly::{boosted_hours, nearest_hours, read_forecast, HourIndex, HourSimilarity, HourlyQuery};
+use predictor::{DayQuery, Predictor, RegressionPredictor, FEATURE_NAMES};
+use significance::{print_significance, significance_report};
+use stats::{demand_report, group_values, print_report, print_wind_report, wind_report, Dimension, ALL_DIMENSIONS};
 
-    input.clear();
-    print!("Enter weather (sunny, rainy, snowy, foggy): ");
-    io::stdout().flush().unwrap();
-    io::stdin().read_line(&mut input).unwrap();
-    let weather = match input.trim().to_lowercase().as_str() {
-        "sunny" => Weather::Sunny,
-        "rainy" => Weather::Rainy,
-        "snowy" => Weather::Snowy,
-        "foggy" => Weather::Foggy,
-        _ => {
-            println!("Invalid weather");
-            Weather::Sunny
+//This function loads the daily summaries and the exact-match graph, from the index when one was given
+fn load_days(options: &Options) -> Result<IndexContents, Box<dyn Error>> {
+    match &options.index {
+        Some(index_path) => {
+            let (contents, status) = load_or_build(&options.data, index_path)?;
+            if let IndexStatus::Built(reason) = status {
+                eprintln!("Rebuilt {} because {}", index_path, reason);
+            }
+            Ok(contents)
         }
-    };
+        None => {
+            let records = data::load_data(&options.data)?;
+            let days = summarize_daily_totals(&records);
+            let graph = build_graph(&days);
+            Ok((records, days, graph))
+        }
+    }
+}
 
-    let mut precipitation = PrecipitationIntensity::None;
-    if matches!(weather, Weather::Rainy | Weather::Snowy) {
-        input.clear();
-        print!("Was it light or heavy {}? (light/heavy): ", if weather == Weather::Rainy { "rain" } else { "snow" });
-        io::stdout().flush().unwrap();
-        io::stdin().read_line(&mut input).unwrap();
-        precipitation = match input.trim().to_lowercase().as_str() {
-            "light" => PrecipitationIntensity::Light,
-            "heavy" => PrecipitationIntensity::Heavy,
-            _ => {
-                println!("Invalid intensity");
-                PrecipitationIntensity::Light //default
+//This function prints the demand statistics, one table per property unless a combination was asked for
+//With tests, each table is followed by whether its differences are significant
+fn print_stats(daily_summaries: &[DaySummary], group_by: &[Dimension], with_tests: bool) {
+    let reports: Vec<Vec<Dimension>> = if group_by.is_empty() {
+        ALL_DIMENSIONS.iter().map(|dimension| vec![*dimension]).collect()
+    } else {
+        vec![group_by.to_vec()]
+    };
+    for dimensions in reports {
+        if let Some(report) = demand_report(daily_summaries, &dimensions) {
+            print_report(&report);
+            if with_tests {
+                if let Some(significance) = significance_report(&group_values(daily_summaries, &dimensions)) {
+                    print_significance(&significance);
+                }
             }
-        };
+            println!();
+        }
     }
+}
 
-    input.clear();
-    print!("Enter temperature (hot, moderate, cold): ");
-    io::stdout().flush().unwrap();
-    io::stdin().read_line(&mut input).unwrap();
-    let temperature = match input.trim().to_lowercase().as_str() {
-        "hot" => TemperatureCategory::Hot,
-        "moderate" => TemperatureCategory::Moderate,
-        "cold" => TemperatureCategory::Cold,
-        _ => {
-            println!("Invalid temperature");
-            TemperatureCategory::Moderate
-        }
-    };
+//This function prints the query that is being answered
+fn print_query(query: &Conditions) {
+    println!("[Finding the closest match]");
+    println!("  - Month: {}", query.month);
+    println!("  - Weather: {}", query.weather);
+    println!("  - Temperature: {}", query.temperature);
+    println!("  - Precipitation: {}", query.precipitation);
+}
+
+//This function runs the bfs and dfs searches for one query on whichever graph was chosen
+fn run_query<G: Adjacency>(graph: &G, days: &[DaySummary], query: &Conditions, options: &Options) {
+    //Uses search algorithm to figure out the estimated total rentals given weather conditions by looking at historical data
+    if let Some(result) = bfs_closest(options.scope, graph, days, query, &options.similarity, &options.exclusion) {
+        println!("[BFS] Closest match: {} - {} rentals (score {:.1}, {} days examined)", result.day.date, result.day.total_rentals, result.score, result.examined);
+    } else {
+        println!("[BFS] No matching day found.");
+    }
 
-    Conditions {
-        month,
-        weather,
-        temperature,
-        precipitation,
+    if let Some(result) = dfs_closest(options.scope, graph, days, query, &options.similarity, &options.exclusion) {
+        println!("[DFS] Closest match: {} - {} rentals (score {:.1}, {} days examined)", result.day.date, result.day.total_rentals, result.score, result.examined);
+    } else {
+        println!("[DFS] No matching day found.");
     }
 }
 
-fn main() {
-    //Loads data
-    let raw_data = match data::load_data("SeoulBikeData 4.csv") {
-        Ok(d) => d,
-        Err(e) => {
-            eprintln!("Failed to load CSV: {}", e);
-            process::exit(1);
+//This function prints the k-nearest estimate for one query
+fn run_predict<G: Adjacency>(graph: &G, days: &[DaySummary], query: &Conditions, options: &Options) {
+    //Instead of relying on a single day, the k nearest days are combined into a weighted estimate
+    if let Some(prediction) = predict(options.scope, graph, days, query, &options.similarity, &options.predict, &options.exclusion) {
+        println!("[{} nearest days out of {} examined]", prediction.neighbours.len(), prediction.examined);
+        for n in &prediction.neighbours {
+            println!("  {} - {} rentals (score {:.1}, weight {:.2})", n.day.date, n.day.total_rentals, n.score, n.weight);
         }
-    };
-    
-    let daily_summaries = summarize_daily_totals(&raw_data);
-    let graph = build_graph(&daily_summaries);
-    let start_index = 0;
-
-    let mut season_totals: HashMap<String, (i32, usize)> = HashMap::new();
-    let mut weather_totals: HashMap<String, (i32, usize)> = HashMap::new();
-    let mut temp_totals: HashMap<String, (i32, usize)> = HashMap::new();
-    let mut rain_totals: HashMap<String, (i32, usize)> = HashMap::new();
-    let mut wind_high = (0, 0);
-    let mut wind_low = (0, 0);
-
-    //The for loop iterates through the daily_summaries and figures out total rentals for all given conditions
-    for day in &daily_summaries {
-
-        let season = match day.month {
-            3..=5 => "Spring",
-            6..=8 => "Summer",
-            9..=11 => "Fall",
-            _ => "Winter",
-        };
-
-        season_totals.entry(season.to_string())
-            .and_modify(|e| { e.0 += day.total_rentals; e.1 += 1 })
-            .or_insert((day.total_rentals, 1));
+        println!("  Weighted mean estimate: {:.0} rentals", prediction.mean);
+        println!("  Weighted median estimate: {:.0} rentals", prediction.median);
+        println!("  {:.0}% prediction interval: {:.0} - {:.0} rentals", options.predict.coverage * 100.0, prediction.lower, prediction.upper);
+    } else {
+        println!("[Prediction] No matching days found.");
+    }
+}
 
-        weather_totals.entry(format!("{}", day.weather))
-            .and_modify(|e| { e.0 += day.total_rentals; e.1 += 1 })
-            .or_insert((day.total_rentals, 1));
+//This function fits the regression on every day and estimates the rentals of the measured day
+fn predict_regression(days: &[DaySummary], query: &DayQuery, lambda: f64) -> Result<(), Box<dyn Error>> {
+    let mut model = RegressionPredictor::new(lambda);
+    model.fit(days)?;
+    println!("[{} regression on {} days, R² {:.3}]", model.name(), days.len(), model.r_squared);
+    println!("  {:<12} {:>10.1}", "intercept", model.intercept);
+    for (name, coefficient) in FEATURE_NAMES.iter().zip(&model.coefficients) {
+        println!("  {:<12} {:>10.1}", name, coefficient);
+    }
+    if let Some(estimate) = model.predict(query) {
+        println!("  Estimate for month {}, {}, {:.1}°C: {:.0} rentals", query.month, query.day_type, query.temperature_c, estimate.max(0.0));
+    }
+    Ok(())
+}
 
-        temp_totals.entry(format!("{}", day.temperature_category))
-            .and_modify(|e| { e.0 += day.total_rentals; e.1 += 1 })
-            .or_insert((day.total_rentals, 1));
+//This function loads the boosted trees from --load-model, or trains them on every hour of the days and saves them to --save-model
+fn boosted_model(records: &[RawRecord], days: &[DaySummary], config: BoostingConfig, options: &Options) -> Result<BoostedPredictor, Box<dyn Error>> {
+    if let Some(path) = &options.load_model {
+        return Ok(BoostedPredictor::with_model(load_model(path)?));
+    }
+    let mut model = BoostedPredictor::new(records, config);
+    model.fit(days)?;
+    if let (Some(path), Some(fitted)) = (&options.save_model, &model.model) {
+        save_model(path, fitted)?;
+        println!("Saved the trees to {}", path);
+    }
+    Ok(model)
+}
 
-        if matches!(day.weather, Weather::Rainy | Weather::Snowy) {
-            rain_totals.entry(format!("{}", day.precipitation))
-                .and_modify(|e| { e.0 += day.total_rentals; e.1 += 1 })
-                .or_insert((day.total_rentals, 1));
+//This function estimates the rentals of the measured day with the boosted trees and shows which features they rely on
+fn predict_boosted(records: &[RawRecord], days: &[DaySummary], query: &DayQuery, config: BoostingConfig, options: &Options) -> Result<(), Box<dyn Error>> {
+    let model = boosted_model(records, days, config, options)?;
+    println!("[{}]", model.name());
+    if let Some(fitted) = &model.model {
+        println!("  Feature importance:");
+        let mut ranked: Vec<(&str, f64)> = HOURLY_FEATURES.iter().copied().zip(fitted.importance.iter().copied()).collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        for (name, share) in ranked {
+            println!("    {:<16} {:>5.1}%", name, share * 100.0);
         }
+    }
+    if let Some(estimate) = model.predict(query) {
+        println!("  Estimate for month {}, {}, {:.1}°C: {:.0} rentals", query.month, query.day_type, query.temperature_c, estimate);
+    }
+    Ok(())
+}
 
-        if day.weather == Weather::Foggy {
-            wind_high.0 += day.total_rentals;
-            wind_high.1 += 1;
-        } else {
-            wind_low.0 += day.total_rentals;
-            wind_low.1 += 1;
+//This function predicts the 24 hours of the forecast day and prints them with the day's total
+fn predict_hours(records: &[RawRecord], days: &[DaySummary], forecast: &str, (month, day_type): (u32, DayType), options: &Options) -> Result<(), Box<dyn Error>> {
+    let query = HourlyQuery { month, day_type, hours: read_forecast(forecast)? };
+    let predictions = match options.model {
+        ModelChoice::Boosted(config) => {
+            let model = boosted_model(records, days, config, options)?;
+            boosted_hours(model.model.as_ref().ok_or("the boosted trees could not be fitted")?, &query)
         }
+        _ => nearest_hours(&HourIndex::new(records), &query, &HourSimilarity::default(), options.predict.k),
+    };
+    println!("[Hourly estimate for month {}, {}]", month, day_type);
+    for prediction in &predictions {
+        println!("  {:02}:00 {:>6.0} rentals", prediction.hour, prediction.rentals);
+    }
+    println!("  Day total: {:.0} rentals", predictions.iter().map(|p| p.rentals).sum::<f64>());
+    if let Some(path) = &options.output {
+        write_rows(path, &predictions)?;
+        println!("  Saved to {}", path);
     }
+    Ok(())
+}
 
-    println!("[Seasonal average bike demand]");
-    let season_order = ["Spring", "Summer", "Fall", "Winter"];
-    for season in &season_order {
-        if let Some((total, count)) = season_totals.get(*season) {
-            let avg = *total as f32 / *count as f32;
-            println!("  {}: {:.0} bikes", season, avg);
-        }
+//This function forecasts the days after the data ends with every chosen method
+fn forecast_days(days: &[DaySummary], horizon: usize, options: &Options) -> Result<(), Box<dyn Error>> {
+    let series = daily_series(days)?;
+    let weather = match &options.forecast {
+        Some(path) => read_weather(path)?,
+        None => Vec::new(),
+    };
+    let coverage = options.predict.coverage;
+    println!("[Forecasting {} days from {} days of history, {} of them filled in]", horizon, series.values.len(), series.filled);
+    let mut forecasts = Vec::new();
+    for method in &options.forecast_methods {
+        let forecast = match method {
+            ForecastMethod::SeasonalNaive => seasonal_naive(&series, horizon, coverage),
+            ForecastMethod::HoltWinters => holt_winters(&series, horizon, coverage),
+            ForecastMethod::WeatherAdjusted => weather_adjusted(days, &series, &weather, horizon, coverage)?,
+        };
+        print_forecast(&forecast, coverage);
+        println!();
+        forecasts.push(forecast);
+    }
+    if let Some(path) = &options.output {
+        write_forecasts(path, &forecasts)?;
+        println!("Saved to {}", path);
     }
+    Ok(())
+}
 
-    println!();
-    println!("[Average daily demand by Weather condition]");
-    for (weather, (total, count)) in &weather_totals {
-        let avg = *total as f32 / *count as f32;
-        println!("  {}: {:.0} bikes", weather, avg);
+//This function backtests the nearest-day searches and the regressions, and the boosted trees when they were asked for
+fn evaluate_estimators(records: &[RawRecord], days: &[DaySummary], options: &Options) -> Result<(), Box<dyn Error>> {
+    let lambda = match options.model {
+        ModelChoice::Regression(lambda) if lambda > 0.0 => lambda,
+        _ => 1.0,
+    };
+    let mut candidates = vec![
+        Candidate::ClosestDay { depth_first: false },
+        Candidate::ClosestDay { depth_first: true },
+        Candidate::NearestDays,
+        Candidate::Model(Box::new(RegressionPredictor::new(0.0))),
+        Candidate::Model(Box::new(RegressionPredictor::new(lambda))),
+    ];
+    if let ModelChoice::Boosted(config) = options.model {
+        candidates.push(Candidate::Model(Box::new(BoostedPredictor::new(records, config))));
     }
+    let settings = SearchSettings { scope: options.scope, similarity: &options.similarity, predict: &options.predict, graph: options.graph, window: options.exclude_window };
+    let board = evaluate(days, &mut candidates, options.scheme, &settings)?;
+    print_leaderboard(&board, options.predict.coverage);
+    Ok(())
+}
 
-    println!();
-    println!("[Average daily demand by temperature category]");
-    for (temp, (total, count)) in &temp_totals {
-        let avg = *total as f32 / *count as f32;
-        println!("  {}: {:.0} bikes", temp, avg);
+//This function runs the search and the prediction for the query on the graph chosen with --graph
+fn answer(days: &[DaySummary], exact_graph: &Graph, query: &Conditions, options: &Options) {
+    match options.graph {
+        None => {
+            run_query(exact_graph, days, query, options);
+            println!();
+            run_predict(exact_graph, days, query, options);
+        }
+        Some(rule) => {
+            let graph = build_weighted_graph(days, &options.similarity, rule);
+            run_query(&graph, days, query, options);
+            println!();
+            run_predict(&graph, days, query, options);
+        }
     }
+}
 
-    println!();
-    println!("Use conditions for prediction:");
-    println!();
+//This function answers every query in the batch file and writes one result line per query
+fn predict_batch(days: &[DaySummary], exact_graph: &Graph, input: &str, output: &str, options: &Options) -> Result<(), Box<dyn Error>> {
+    let queries = read_conditions(input)?;
+    let results = match options.graph {
+        None => run_batch(exact_graph, days, &queries, options.scope, &options.similarity, &options.predict, &options.exclusion, options.depth_first),
+        Some(rule) => {
+            let graph = build_weighted_graph(days, &options.similarity, rule);
+            run_batch(&graph, days, &queries, options.scope, &options.similarity, &options.predict, &options.exclusion, options.depth_first)
+        }
+    };
+    write_rows(output, &results)?;
+    println!("Answered {} queries from {} into {}", results.len(), input, output);
+    Ok(())
+}
 
-    //User Input starts here
-    let query = user_input();
+//This function writes the chosen graph to a file for Graphviz or Gephi
+fn export(days: &[DaySummary], exact_graph: &Graph, path: &str, options: &Options) -> Result<(), Box<dyn Error>> {
+    let edges = match options.graph {
+        None => exact_edges(exact_graph),
+        Some(rule) => weighted_edges(&build_weighted_graph(days, &options.similarity, rule)),
+    };
+    export_graph(path, options.format, days, &edges)?;
+    println!("Wrote {} days and {} edges to {}", days.len(), edges.len(), path);
+    Ok(())
+}
 
+//This function shows which weather regimes form separate groups of days
+fn analyze_graph(days: &[DaySummary], exact_graph: &Graph, options: &Options) {
+    let weighted = build_weighted_graph(days, &options.similarity, options.graph.unwrap_or(EdgeRule::Nearest(5)));
+    let (components, degrees) = match options.graph {
+        None => (connected_components(exact_graph, days.len()), degree_distribution(exact_graph, days.len())),
+        Some(_) => (connected_components(&weighted, days.len()), degree_distribution(&weighted, days.len())),
+    };
+    print_group_table("Connected components", &summarize_groups(&components, days), 15);
     println!();
-    println!("[Finding the closest match]");
-    println!("  - Month: {}", query.month);
-    println!("  - Weather: {}", query.weather);
-    println!("  - Temperature: {}", query.temperature);
-    println!("  - Precipitation: {}", query.precipitation);
+    print_degree_table(&degrees);
+    println!();
+    let communities = label_propagation(&weighted, days.len(), 50);
+    print_group_table("Communities (label propagation on the weighted graph)", &summarize_groups(&communities, days), 15);
+    println!("  Modularity: {:.3}", modularity(&weighted, &communities));
+}
 
-    //Uses search algorithm to figure out the estimated total rentals given weather conditions by looking at historical data
-    if let Some(day) = bfs_closest(start_index, &graph, &daily_summaries, &query) {
-        println!("[BFS] Closest match: {} - {} rentals", day.date, day.total_rentals);
+//This function shows how one day turns into another through a chain of similar days
+fn graph_path(days: &[DaySummary], from: &str, to: &str, options: &Options) -> Result<(), Box<dyn Error>> {
+    let start = find_day(days, from).ok_or_else(|| format!("{} is not in the dataset (dd/mm/yyyy)", from))?;
+    let goal = find_day(days, to).ok_or_else(|| format!("{} is not in the dataset (dd/mm/yyyy)", to))?;
+    //The exact-match graph has no weights, so paths fall back to the k-nearest graph unless a weighted one was chosen
+    let rule = options.graph.unwrap_or(EdgeRule::Nearest(5));
+    let graph = build_weighted_graph(days, &options.similarity, rule);
+    let path = if options.astar {
+        astar_path(&graph, days, start, goal, &options.similarity)
     } else {
-        println!("[BFS] No matching day found.");
+        dijkstra_path(&graph, days, start, goal)
+    };
+    match (path, degrees_of_separation(&graph, start, goal)) {
+        (Some(path), Some(hops)) => {
+            println!("[Path from {} to {}: {} degrees of separation, distance {:.2}, {} days expanded]", from, to, hops, path.total_distance, path.expanded);
+            for step in &path.steps {
+                let day = &step.day;
+                println!(
+                    "  {} - {} rentals, {}, {}, {} precipitation, {:.1}°C (cumulative distance {:.2})",
+                    day.date, day.total_rentals, day.weather, day.temperature_category, day.precipitation, day.avg_temperature, step.cumulative
+                );
+            }
+            Ok(())
+        }
+        _ => Err(format!("{} and {} are not connected in this graph", from, to).into()),
     }
-    
-    if let Some(day) = dfs_closest(start_index, &graph, &daily_summaries, &query) {
-        println!("[DFS] Closest match: {} - {} rentals", day.date, day.total_rentals);
-    } else {
-        println!("[DFS] No matching day found.");
+}
+
+//This function picks the most central days of each component or community as the canonical days of that regime
+fn graph_representatives(days: &[DaySummary], exact_graph: &Graph, options: &Options) {
+    let n = days.len();
+    let measure = options.measure;
+    let weighted = build_weighted_graph(days, &options.similarity, options.graph.unwrap_or(EdgeRule::Nearest(5)));
+    //Communities only exist on the weighted graph, so their days are scored on that graph too
+    let (groups, scores) = match (options.graph, options.by_community) {
+        (None, false) => (connected_components(exact_graph, n), centrality(exact_graph, n, measure)),
+        (_, true) => (label_propagation(&weighted, n, 50), centrality(&weighted, n, measure)),
+        (Some(_), false) => (connected_components(&weighted, n), centrality(&weighted, n, measure)),
+    };
+    let picks = representative_days(&groups, &scores, days, options.per_group);
+    let title = format!("Most representative days by {:?} centrality per {}", measure, if options.by_community { "community" } else { "component" });
+    print_representatives(&title, &summarize_groups(&groups, days), &picks, &scores, days, 20);
+}
+
+//This function learns the weights with leave-one-out prediction and writes them to a file that --similarity-config can load
+fn fit(days: &[DaySummary], path: &str, options: &Options) -> Result<(), Box<dyn Error>> {
+    let fit_options = FitOptions { predict: options.predict.clone(), ..FitOptions::default() };
+    println!("[Fitting similarity weights on {} days, k = {}, {:?} weighting]", days.len(), fit_options.predict.k, fit_options.predict.weighting);
+    let report = fit_weights(days, &options.similarity, &fit_options);
+    println!("  Mean absolute error before: {:.0} rentals", report.error_before);
+    println!("  Mean absolute error after: {:.0} rentals ({} rounds)", report.error_after, report.rounds);
+    for (key, value) in report.config.entries() {
+        println!("  {} = {}", key, value);
+    }
+    let header = format!(
+        "Learned by leave-one-out {}-nearest-day prediction with {:?} weighting\nMean absolute error {:.0} -> {:.0}",
+        fit_options.predict.k, fit_options.predict.weighting, report.error_before, report.error_after
+    );
+    save_config(path, &report.config, &header)?;
+    println!("  Saved to {}", path);
+    Ok(())
+}
+
+//This function runs the command once the command line has been read
+fn run(command: Command, options: &Options) -> Result<(), Box<dyn Error>> {
+    if command == Command::Help {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    let (records, daily_summaries, exact_graph) = load_days(options).map_err(|e| format!("Failed to load CSV {}: {}", options.data, e))?;
+
+    //parse_args already checked that every command has the options it needs, so the expects below cannot fail
+    match command {
+        Command::Stats => {
+            print_stats(&daily_summaries, &options.group_by, true);
+            //The wind analysis needs the hourly records rather than the daily summaries
+            if let Some(report) = wind_report(&records) {
+                print_wind_report(&report);
+            }
+        }
+        Command::Analyze => {
+            let report = analyze(&records)?;
+            print_analysis(&report);
+            if let Some(path) = &options.output {
+                write_analysis(path, &report)?;
+                println!("  Saved to {}", path);
+            }
+        }
+        Command::Query => {
+            let query = options.query.as_ref().expect("checked by parse_args");
+            print_query(query);
+            match options.graph {
+                None => run_query(&exact_graph, &daily_summaries, query, options),
+                Some(rule) => run_query(&build_weighted_graph(&daily_summaries, &options.similarity, rule), &daily_summaries, query, options),
+            }
+        }
+        Command::Predict if options.model != ModelChoice::NearestDays => {
+            let query = options.day_query.as_ref().expect("checked by parse_args");
+            match options.model {
+                ModelChoice::Regression(lambda) => predict_regression(&daily_summaries, query, lambda)?,
+                ModelChoice::Boosted(config) => predict_boosted(&records, &daily_summaries, query, config, options)?,
+                ModelChoice::NearestDays => unreachable!(),
+            }
+        }
+        Command::Predict => match (&options.batch, &options.output, &options.query) {
+            (Some(input), Some(output), _) => predict_batch(&daily_summaries, &exact_graph, input, output, options)?,
+            (_, _, Some(query)) => match options.graph {
+                None => run_predict(&exact_graph, &daily_summaries, query, options),
+                Some(rule) => run_predict(&build_weighted_graph(&daily_summaries, &options.similarity, rule), &daily_summaries, query, options),
+            },
+            _ => unreachable!("checked by parse_args"),
+        },
+        Command::PredictHours => {
+            let forecast = options.forecast.as_deref().expect("checked by parse_args");
+            predict_hours(&records, &daily_summaries, forecast, options.forecast_day.expect("checked by parse_args"), options)?
+        }
+        Command::Forecast => forecast_days(&daily_summaries, options.forecast_days.expect("checked by parse_args"), options)?,
+        Command::Evaluate => evaluate_estimators(&records, &daily_summaries, options)?,
+        Command::Export => export(&daily_summaries, &exact_graph, options.output.as_deref().expect("checked by parse_args"), options)?,
+        Command::Graph(GraphAction::Analyze) => analyze_graph(&daily_summaries, &exact_graph, options),
+        Command::Graph(GraphAction::Path) => {
+            let (from, to) = options.path.as_ref().expect("checked by parse_args");
+            graph_path(&daily_summaries, from, to, options)?
+        }
+        Command::Graph(GraphAction::Representatives) => graph_representatives(&daily_summaries, &exact_graph, options),
+        Command::FitWeights => fit(&daily_summaries, options.output.as_deref().expect("checked by parse_args"), options)?,
+        Command::Interactive => {
+            print_stats(&daily_summaries, &options.group_by, false);
+            println!("Use conditions for prediction (enter ? for help, q to quit, or press enter to keep the value in brackets):");
+            println!();
+
+            //User Input starts here, the data stays loaded so any number of queries can be asked
+            let stdin = std::io::stdin();
+            let mut prompt = Prompt::new(stdin.lock(), std::io::stdout());
+            let mut previous = starting_conditions();
+            while let Some(query) = prompt.conditions(&previous) {
+                println!();
+                print_query(&query);
+                answer(&daily_summaries, &exact_graph, &query, options);
+                println!();
+                previous = query;
+            }
+        }
+        Command::Help => {}
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (command, options) = match parse_args(&args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = run(command, &options) {
+        eprintln!("{}", e);
+        process::exit(1);
     }
 }
@@ -1,11 +1,13 @@
 //This module specifies logic used to classify the weather conditions into different groups, which will be used for search algorithms
-use crate::common::{RawRecord, DaySummary, Weather, TemperatureCategory, PrecipitationIntensity};
+use crate::common::{RawRecord, DaySummary, DayType, Weather, TemperatureCategory, PrecipitationIntensity};
 use std::collections::HashMap;
 
 //This function takes in data (raw record) as the input and then returns the vector after summarizing daily totals
 //This step is crucial because the original data has separate rows for each hour, but by getting daily totals, we look at daily bike demands
 pub fn summarize_daily_totals(data: &[RawRecord]) -> Vec<DaySummary> {
     let mut daily_map: HashMap<String, DaySummary> = HashMap::new();
+    //temperature and humidity are averaged over the hours of the day, so their sums are kept with the number of hours
+    let mut hourly_sums: HashMap<String, (f64, f64, u32)> = HashMap::new();
 
     //The for loop, for every "data", does four main jobs.
     for record in data {
@@ -42,9 +44,17 @@ pub fn summarize_daily_totals(data: &[RawRecord]) -> Vec<DaySummary> {
             TemperatureCategory::Moderate
         };
 
-        //The code below attempts to concatenate each hourly rented bike count for each respective day to get daily totals
+        hourly_sums.entry(date_key.clone())
+            .and_modify(|e| { e.0 += record.temperature; e.1 += record.humidity; e.2 += 1 })
+            .or_insert((record.temperature, record.humidity, 1));
+
+        //The code below attempts to concatenate each hourly rented bike count and rainfall for each respective day to get daily totals
         daily_map.entry(date_key.clone())
-            .and_modify(|day| day.total_rentals += record.rented_bike_count)
+            .and_modify(|day| {
+                day.total_rentals += record.rented_bike_count;
+                day.total_rainfall += record.rainfall;
+                day.total_snowfall += record.snowfall;
+            })
             .or_insert(DaySummary {
                 date: date_key,
                 month: record.month,
@@ -52,8 +62,157 @@ pub fn summarize_daily_totals(data: &[RawRecord]) -> Vec<DaySummary> {
                 weather,
                 temperature_category,
                 precipitation,
+                avg_temperature: 0.0,
+                total_rainfall: record.rainfall,
+                total_snowfall: record.snowfall,
+                avg_humidity: 0.0,
+                day_type: day_type(&record.date, &record.holiday),
             });
     }
-    //These are now added to the output vector
-    daily_map.into_values().collect()
+
+    //The averages are filled in once every hour of the day has been seen
+    for (date, (temperature, humidity, hours)) in hourly_sums {
+        if let Some(day) = daily_map.get_mut(&date) {
+            day.avg_temperature = temperature / hours as f64;
+            day.avg_humidity = humidity / hours as f64;
+        }
+    }
+    //These are now added to the output vector in date order, since the map's own order changes from run to run
+    //and a day's position decides the start of a component search and every tie-break by index
+    let mut days: Vec<DaySummary> = daily_map.into_values().collect();
+    days.sort_by(|a, b| day_number(&a.date).cmp(&day_number(&b.date)).then_with(|| a.date.cmp(&b.date)));
+    days
+}
+
+//This function finds the day of the week of a dd/mm/yyyy date with Sakamoto's method, 0 being Sunday
+//The method counts from year 1, so years outside 1..=9999 give None instead of wrapping around
+fn weekday(date: &str) -> Option<i64> {
+    let mut parts = date.split('/').map(|part| part.trim().parse::<i64>());
+    let (day, month, year) = (parts.next()?.ok()?, parts.next()?.ok()?, parts.next()?.ok()?);
+    if !(1..=12).contains(&month) || day < 1 || !(1..=9999).contains(&year) {
+        return None;
+    }
+    const OFFSETS: [i64; 12] = [0, 3, 2, 5, 0, 3, 5, 1, 4, 6, 2, 4];
+    let year = if month < 3 { year - 1 } else { year };
+    Some((year + year / 4 - year / 100 + year / 400 + OFFSETS[month as usize - 1] + day) % 7)
+}
+
+//This function labels a day as a holiday when the dataset says so, and otherwise as a weekday or weekend
+pub fn day_type(date: &str, holiday: &str) -> DayType {
+    if holiday.trim() == "Holiday" {
+        return DayType::Holiday;
+    }
+    match weekday(date) {
+        Some(0) | Some(6) => DayType::Weekend,
+        _ => DayType::Weekday,
+    }
+}
+
+//This function gives the number of days in a month, with February following the Gregorian leap years
+fn days_in_month(month: i64, year: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+//This function counts the days from 01/01/1970 to a dd/mm/yyyy date, so dates can be ordered and subtracted
+//Dates that do not exist, like 31/02/2018, give None
+pub fn day_number(date: &str) -> Option<i64> {
+    let mut parts = date.split('/').map(|part| part.trim().parse::<i64>());
+    let (day, month, year) = (parts.next()?.ok()?, parts.next()?.ok()?, parts.next()?.ok()?);
+    if !(1..=12).contains(&month) || !(1..=days_in_month(month, year)).contains(&day) {
+        return None;
+    }
+    //Counting from March puts the leap day at the end of the year
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Some(era * 146097 + day_of_era - 719468)
+}
+
+//This function turns a day number back into a dd/mm/yyyy date
+pub fn date_of(number: i64) -> String {
+    let shifted = number + 719468;
+    let era = shifted.div_euclid(146097);
+    let day_of_era = shifted - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:02}/{:02}/{}", day, month, year)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::{bfs_closest, build_graph, Exclusion, Scope};
+    use crate::similarity::{conditions_of, SimilarityConfig};
+
+    fn record(date: &str, rented_bike_count: i32, rainfall: f64) -> RawRecord {
+        RawRecord {
+            date: date.to_string(),
+            rented_bike_count,
+            hour: 12,
+            temperature: 15.0,
+            humidity: 50.0,
+            wind_speed: 1.0,
+            visibility: 2000.0,
+            dew_point_temp: 5.0,
+            solar_radiation: 0.0,
+            rainfall,
+            snowfall: 0.0,
+            seasons: "Spring".to_string(),
+            holiday: "No Holiday".to_string(),
+            functioning_day: "Yes".to_string(),
+            month: date[3..5].parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_days_come_back_in_date_order() {
+        let records: Vec<RawRecord> = (1..=20).rev().map(|d| record(&format!("{:02}/04/2018", d), 100 * d, if d % 3 == 0 { 2.0 } else { 0.0 })).collect();
+        let first = summarize_daily_totals(&records);
+        let second = summarize_daily_totals(&records);
+        let dates: Vec<&str> = first.iter().map(|day| day.date.as_str()).collect();
+        assert_eq!(dates, (1..=20).map(|d| format!("{:02}/04/2018", d)).collect::<Vec<_>>());
+        assert_eq!(dates, second.iter().map(|day| day.date.as_str()).collect::<Vec<_>>());
+
+        //the component search starts from the first day, so it finds the same day on every run
+        let rainy = conditions_of(&first[2]);
+        let search = |days: &[DaySummary]| {
+            bfs_closest(Scope::Component(0), &build_graph(days), days, &rainy, &SimilarityConfig::default(), &Exclusion::default()).map(|r| r.day.date)
+        };
+        assert_eq!(search(&first), Some("01/04/2018".to_string()));
+        assert_eq!(search(&first), search(&second));
+    }
+
+    #[test]
+    fn test_day_type() {
+        //01/12/2017 was a Friday, 02/12/2017 a Saturday and 25/12/2017 Christmas
+        assert_eq!(day_type("01/12/2017", "No Holiday"), DayType::Weekday);
+        assert_eq!(day_type("02/12/2017", "No Holiday"), DayType::Weekend);
+        assert_eq!(day_type("03/12/2017", "No Holiday"), DayType::Weekend);
+        assert_eq!(day_type("25/12/2017", "Holiday"), DayType::Holiday);
+        assert_eq!(day_type("29/02/2016", "No Holiday"), DayType::Weekday);
+        assert_eq!(weekday("01/01/0001"), Some(1));
+        assert_eq!(weekday("15/01/0000"), None);
+        assert_eq!(weekday("-5/01/2018"), None);
+        assert_eq!(day_type("15/01/0000", "No Holiday"), DayType::Weekday);
+        assert_eq!(day_number("01/01/1970"), Some(0));
+        assert_eq!(day_number("01/03/2018").unwrap() - day_number("28/02/2018").unwrap(), 1);
+        assert_eq!(date_of(day_number("29/02/2016").unwrap() + 1), "01/03/2016");
+        assert_eq!(date_of(day_number("31/12/2017").unwrap() + 1), "01/01/2018");
+        assert_eq!(day_number("31/02/2018"), None);
+        assert_eq!(day_number("29/02/2018"), None);
+        assert_eq!(day_number("31/04/2018"), None);
+        assert!(day_number("29/02/2000").is_some());
+        assert_eq!(day_number("29/02/1900"), None);
+    }
 }
@@ -0,0 +1,191 @@
+//This module holds the models that estimate the rentals of a day from its measured weather, as an alternative to searching for similar days
+//Every model implements Predictor, so they can be trained and compared the same way
+use crate::analysis::invert;
+use crate::common::{DaySummary, DayType};
+
+//This struct is the day to estimate, with the measured values the models use
+#[derive(Debug, Clone, PartialEq)]
+pub struct DayQuery {
+    pub month: u32,
+    pub day_type: DayType,
+    pub temperature_c: f64,
+    pub rainfall_mm: f64,
+    pub snowfall_cm: f64,
+    //None uses the average humidity of the training days
+    pub humidity: Option<f64>,
+}
+
+impl DayQuery {
+    //This function describes a known day as a query, which is how models are tested on past days
+    pub fn of_day(day: &DaySummary) -> DayQuery {
+        DayQuery {
+            month: day.month,
+            day_type: day.day_type,
+            temperature_c: day.avg_temperature,
+            rainfall_mm: day.total_rainfall,
+            snowfall_cm: day.total_snowfall,
+            humidity: Some(day.avg_humidity),
+        }
+    }
+}
+
+//This trait is a model that learns from past days and then estimates the total rentals of a new one
+pub trait Predictor {
+    fn name(&self) -> String;
+    fn fit(&mut self, days: &[DaySummary]) -> Result<(), String>;
+    //None until the model has been fitted
+    fn predict(&self, query: &DayQuery) -> Option<f64>;
+}
+
+//Names of the daily features, in the order features() produces them; January and weekdays are the baselines of the one-hot columns
+pub const FEATURE_NAMES: [&str; 17] = [
+    "temperature", "rainfall", "snowfall", "humidity",
+    "february", "march", "april", "may", "june", "july", "august", "september", "october", "november", "december",
+    "weekend", "holiday",
+];
+
+//Column ranges of the month and day type one-hot features
+const ONE_HOT_GROUPS: [std::ops::Range<usize>; 2] = [4..15, 15..17];
+
+//This function turns a query into the feature vector, filling in the humidity when it is missing
+fn features(query: &DayQuery, default_humidity: f64) -> Vec<f64> {
+    let mut row = vec![query.temperature_c, query.rainfall_mm, query.snowfall_cm, query.humidity.unwrap_or(default_humidity)];
+    row.extend((2..=12).map(|month| if query.month == month { 1.0 } else { 0.0 }));
+    row.push(if query.day_type == DayType::Weekend { 1.0 } else { 0.0 });
+    row.push(if query.day_type == DayType::Holiday { 1.0 } else { 0.0 });
+    row
+}
+
+//This struct is a linear model of the daily rentals, fitted by least squares or by ridge regression when the penalty is above 0
+//The penalty shrinks the coefficients towards 0, which keeps months with few training days from getting extreme values
+#[derive(Debug, Clone)]
+pub struct RegressionPredictor {
+    pub lambda: f64,
+    pub intercept: f64,
+    //one coefficient per feature in the original units, 0 for features that never changed in the training days
+    pub coefficients: Vec<f64>,
+    pub r_squared: f64,
+    mean_humidity: f64,
+    fitted: bool,
+}
+
+impl RegressionPredictor {
+    pub fn new(lambda: f64) -> RegressionPredictor {
+        RegressionPredictor { lambda, intercept: 0.0, coefficients: Vec::new(), r_squared: 0.0, mean_humidity: 0.0, fitted: false }
+    }
+
+    fn value(&self, row: &[f64]) -> f64 {
+        self.intercept + self.coefficients.iter().zip(row).map(|(c, x)| c * x).sum::<f64>()
+    }
+}
+
+impl Predictor for RegressionPredictor {
+    fn name(&self) -> String {
+        if self.lambda > 0.0 { format!("ridge (lambda {})", self.lambda) } else { "linear".to_string() }
+    }
+
+    //The features are standardised before fitting so the penalty treats them alike, then the coefficients are converted back
+    fn fit(&mut self, days: &[DaySummary]) -> Result<(), String> {
+        if days.is_empty() {
+            return Err("no days to fit on".to_string());
+        }
+        let n = days.len() as f64;
+        self.mean_humidity = days.iter().map(|d| d.avg_humidity).sum::<f64>() / n;
+        let rows: Vec<Vec<f64>> = days.iter().map(|d| features(&DayQuery::of_day(d), self.mean_humidity)).collect();
+        let y: Vec<f64> = days.iter().map(|d| d.total_rentals as f64).collect();
+        let mean_y = y.iter().sum::<f64>() / n;
+
+        let width = FEATURE_NAMES.len();
+        let means: Vec<f64> = (0..width).map(|j| rows.iter().map(|r| r[j]).sum::<f64>() / n).collect();
+        let spreads: Vec<f64> = (0..width).map(|j| (rows.iter().map(|r| (r[j] - means[j]).powi(2)).sum::<f64>() / n).sqrt()).collect();
+        //Features that never change (a month missing from the training days) carry no information and are left out
+        let mut active: Vec<usize> = (0..width).filter(|&j| spreads[j] > 0.0).collect();
+        //When the baseline of a one-hot group (January, weekdays) is missing too, one of the remaining columns becomes the baseline instead
+        for group in ONE_HOT_GROUPS {
+            let baseline_missing = rows.iter().all(|row| row[group.clone()].contains(&1.0));
+            if let Some(position) = active.iter().position(|j| group.contains(j)).filter(|_| baseline_missing) {
+                active.remove(position);
+            }
+        }
+        let p = active.len();
+        if days.len() <= p && self.lambda == 0.0 {
+            return Err(format!("{} days are too few for {} coefficients without a penalty", days.len(), p + 1));
+        }
+
+        let mut xtx = vec![vec![0.0; p]; p];
+        let mut xty = vec![0.0; p];
+        for (row, target) in rows.iter().zip(&y) {
+            let z: Vec<f64> = active.iter().map(|&j| (row[j] - means[j]) / spreads[j]).collect();
+            for a in 0..p {
+                xty[a] += z[a] * (target - mean_y);
+                for b in 0..p {
+                    xtx[a][b] += z[a] * z[b];
+                }
+            }
+        }
+        for (a, row) in xtx.iter_mut().enumerate() {
+            row[a] += self.lambda;
+        }
+        let inverse = invert(&xtx).ok_or("the features are linearly dependent, try a ridge penalty above 0")?;
+
+        self.coefficients = vec![0.0; width];
+        for (a, &j) in active.iter().enumerate() {
+            let standardised: f64 = (0..p).map(|b| inverse[a][b] * xty[b]).sum();
+            self.coefficients[j] = standardised / spreads[j];
+        }
+        self.intercept = mean_y - self.coefficients.iter().zip(&means).map(|(c, m)| c * m).sum::<f64>();
+        self.fitted = true;
+
+        let residual: f64 = rows.iter().zip(&y).map(|(row, target)| (target - self.value(row)).powi(2)).sum();
+        let total: f64 = y.iter().map(|target| (target - mean_y).powi(2)).sum();
+        self.r_squared = if total > 0.0 { 1.0 - residual / total } else { 0.0 };
+        Ok(())
+    }
+
+    fn predict(&self, query: &DayQuery) -> Option<f64> {
+        if !self.fitted {
+            return None;
+        }
+        Some(self.value(&features(query, self.mean_humidity)))
+    }
+}
+
+//The tests check that least squares recovers known effects and that the ridge penalty shrinks them
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_day;
+
+    fn make_day(month: u32, temperature: f64, rainfall: f64, day_type: DayType) -> DaySummary {
+        let weekend_drop = if day_type == DayType::Weekday { 0.0 } else { 3000.0 };
+        DaySummary {
+            month,
+            avg_temperature: temperature,
+            total_rainfall: rainfall,
+            avg_humidity: 40.0 + (temperature * 7.0) % 30.0,
+            day_type,
+            ..test_day("", (10000.0 + 500.0 * temperature - 200.0 * rainfall - weekend_drop) as i32)
+        }
+    }
+
+    #[test]
+    fn test_regression_predictor() {
+        let days: Vec<DaySummary> = (0..60)
+            .map(|i| make_day(5 + i % 3, (i % 17) as f64, ((i * 5) % 7) as f64, if i % 4 == 0 { DayType::Weekend } else { DayType::Weekday }))
+            .collect();
+        let mut linear = RegressionPredictor::new(0.0);
+        assert!(linear.predict(&DayQuery::of_day(&days[0])).is_none());
+        linear.fit(&days).unwrap();
+        assert!((linear.coefficients[0] - 500.0).abs() < 1e-6);
+        assert!((linear.coefficients[1] + 200.0).abs() < 1e-6);
+        assert!((linear.coefficients[15] + 3000.0).abs() < 1e-6);
+        assert!(linear.r_squared > 0.9999);
+        let query = DayQuery { month: 6, day_type: DayType::Weekday, temperature_c: 20.0, rainfall_mm: 0.0, snowfall_cm: 0.0, humidity: None };
+        assert!((linear.predict(&query).unwrap() - 20000.0).abs() < 1.0);
+
+        let mut ridge = RegressionPredictor::new(50.0);
+        ridge.fit(&days).unwrap();
+        assert!(ridge.coefficients[0].abs() < linear.coefficients[0].abs());
+        assert!(ridge.name().starts_with("ridge"));
+    }
+}
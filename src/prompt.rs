@@ -0,0 +1,196 @@
+//This module asks for the query conditions interactively
+//Every question re-asks on invalid input, accepts abbreviations or the number of a choice, shows a default and explains itself on '?'
+use std::io::{BufRead, Write};
+
+use crate::common::{Conditions, PrecipitationIntensity, TemperatureCategory, Weather};
+
+const MONTHS: [(&str, u32); 12] = [
+    ("January", 1), ("February", 2), ("March", 3), ("April", 4), ("May", 5), ("June", 6),
+    ("July", 7), ("August", 8), ("September", 9), ("October", 10), ("November", 11), ("December", 12),
+];
+const WEATHERS: [(&str, Weather); 4] = [
+    ("sunny", Weather::Sunny), ("rainy", Weather::Rainy), ("snowy", Weather::Snowy), ("foggy", Weather::Foggy),
+];
+const TEMPERATURES: [(&str, TemperatureCategory); 3] = [
+    ("hot", TemperatureCategory::Hot), ("moderate", TemperatureCategory::Moderate), ("cold", TemperatureCategory::Cold),
+];
+const INTENSITIES: [(&str, PrecipitationIntensity); 2] = [
+    ("light", PrecipitationIntensity::Light), ("heavy", PrecipitationIntensity::Heavy),
+];
+
+//Typing one of these at any question ends the interactive session
+const QUIT: [&str; 3] = ["q", "quit", "exit"];
+
+//This enum is what one answer turned out to be
+#[derive(Debug, PartialEq)]
+enum Answer<T> {
+    Value(T),
+    Retry(String),
+    Quit,
+}
+
+//This struct reads answers from any input so the prompt can be tested without a terminal
+pub struct Prompt<R: BufRead, W: Write> {
+    input: R,
+    output: W,
+}
+
+//This function gives the defaults offered before the first query has been answered
+pub fn starting_conditions() -> Conditions {
+    Conditions {
+        month: 1,
+        weather: Weather::Sunny,
+        temperature: TemperatureCategory::Moderate,
+        precipitation: PrecipitationIntensity::None,
+        temperature_c: None,
+        rainfall_mm: None,
+    }
+}
+
+//This function matches the text against the choices by full name, unique prefix or 1-based number
+fn pick<T: Clone>(text: &str, choices: &[(&str, T)]) -> Result<T, String> {
+    let text = text.to_lowercase();
+    if let Ok(number) = text.parse::<usize>() {
+        if (1..=choices.len()).contains(&number) {
+            return Ok(choices[number - 1].1.clone());
+        }
+        return Err(format!("Please enter a number from 1 to {}", choices.len()));
+    }
+    let matches: Vec<&(&str, T)> = choices.iter().filter(|(name, _)| name.to_lowercase().starts_with(&text)).collect();
+    match matches.as_slice() {
+        [(_, value)] => Ok(value.clone()),
+        [] => Err(format!("'{}' is not one of {}", text, names(choices))),
+        _ => Err(format!("'{}' could mean {}", text, matches.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(" or "))),
+    }
+}
+
+//This function lists the choices with their numbers for the question and the help text
+fn names<T>(choices: &[(&str, T)]) -> String {
+    choices.iter().enumerate().map(|(i, (name, _))| format!("{}={}", i + 1, name)).collect::<Vec<_>>().join(", ")
+}
+
+impl<R: BufRead, W: Write> Prompt<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Prompt { input, output }
+    }
+
+    //This function keeps asking one question until the parser accepts the answer
+    //An empty line takes the default, '?' prints the help and end of input counts as quitting
+    fn ask<T: Clone>(&mut self, question: &str, default: &str, help: &str, parse: impl Fn(&str) -> Answer<T>, default_value: T) -> Option<T> {
+        loop {
+            write!(self.output, "{} [{}]: ", question, default).ok()?;
+            self.output.flush().ok()?;
+            let mut line = String::new();
+            if self.input.read_line(&mut line).ok()? == 0 {
+                return None;
+            }
+            let text = line.trim();
+            let answer = match text {
+                "" => Answer::Value(default_value.clone()),
+                "?" => Answer::Retry(help.to_string()),
+                _ if QUIT.contains(&text.to_lowercase().as_str()) => Answer::Quit,
+                _ => parse(text),
+            };
+            match answer {
+                Answer::Value(value) => return Some(value),
+                Answer::Quit => return None,
+                Answer::Retry(message) => writeln!(self.output, "  {}", message).ok()?,
+            }
+        }
+    }
+
+    //This function asks for one of a fixed list of choices
+    fn choose<T: Clone + PartialEq>(&mut self, question: &str, help: &str, choices: &[(&str, T)], default: &T) -> Option<T> {
+        let default_name = choices.iter().find(|(_, value)| value == default).map_or("", |(name, _)| *name);
+        //Long lists such as the months would not fit on one line, so only the range is shown until '?' is asked
+        let listing = if choices.len() > 4 { format!("1-{} or a name", choices.len()) } else { names(choices) };
+        let question = format!("{} ({})", question, listing);
+        let help = format!("{} Choices: {}. Abbreviations such as '{}' work too.", help, names(choices), &choices[0].0[..3]);
+        self.ask(&question, default_name, &help, |text| match pick(text, choices) {
+            Ok(value) => Answer::Value(value),
+            Err(message) => Answer::Retry(message),
+        }, default.clone())
+    }
+
+    //This function asks for a measurement that may be left out with 'none'
+    fn measurement(&mut self, question: &str, help: &str, default: Option<f64>) -> Option<Option<f64>> {
+        let shown = default.map_or("none".to_string(), |value| value.to_string());
+        self.ask(question, &shown, help, |text| match text.to_lowercase().as_str() {
+            "none" | "-" => Answer::Value(None),
+            _ => match text.parse::<f64>() {
+                Ok(value) if value.is_finite() => Answer::Value(Some(value)),
+                _ => Answer::Retry(format!("'{}' is not a number, enter 'none' to leave it out", text)),
+            },
+        }, default)
+    }
+
+    //This function asks for every condition of one query, offering the previous answers as defaults
+    //Returns None once the user quits
+    pub fn conditions(&mut self, previous: &Conditions) -> Option<Conditions> {
+        let month = self.choose("Month", "The month of the day you want to estimate.", &MONTHS, &previous.month)?;
+        let weather = self.choose("Weather", "The weather label of the day.", &WEATHERS, &previous.weather)?;
+
+        let mut precipitation = PrecipitationIntensity::None;
+        if matches!(weather, Weather::Rainy | Weather::Snowy) {
+            let default = if previous.precipitation == PrecipitationIntensity::None { PrecipitationIntensity::Light } else { previous.precipitation.clone() };
+            let question = if weather == Weather::Rainy { "Rain intensity" } else { "Snow intensity" };
+            precipitation = self.choose(question, "How strong the rain or snow was.", &INTENSITIES, &default)?;
+        }
+
+        let temperature = self.choose("Temperature", "Hot is above 25°C and cold is below 10°C.", &TEMPERATURES, &previous.temperature)?;
+
+        //The measured values are optional and only change the score when their weights are set
+        let temperature_c = self.measurement("Temperature in °C", "The average temperature of the day, or 'none'.", previous.temperature_c)?;
+        let rainfall_mm = self.measurement("Rainfall in mm", "The total rainfall of the day, or 'none'.", previous.rainfall_mm)?;
+
+        Some(Conditions {
+            month,
+            weather,
+            temperature,
+            precipitation,
+            temperature_c,
+            rainfall_mm,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    //This function answers one query from the given lines and returns it with what was printed
+    fn run(lines: &str, previous: &Conditions) -> (Option<Conditions>, String) {
+        let mut output = Vec::new();
+        let result = Prompt::new(Cursor::new(lines.to_string()), &mut output).conditions(previous);
+        (result, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_reasks_and_accepts_abbreviations() {
+        let (result, printed) = run("0\njul\ns\nsn\n2\n?\nh\n-3.5\n\n", &starting_conditions());
+        let query = result.unwrap();
+        assert_eq!(query.month, 7);
+        assert_eq!(query.weather, Weather::Snowy);
+        assert_eq!(query.precipitation, PrecipitationIntensity::Heavy);
+        assert_eq!(query.temperature, TemperatureCategory::Hot);
+        assert_eq!(query.temperature_c, Some(-3.5));
+        assert_eq!(query.rainfall_mm, None);
+        assert!(printed.contains("from 1 to 12"));
+        assert!(printed.contains("could mean sunny or snowy"));
+        assert!(printed.contains("Choices:"));
+    }
+
+    #[test]
+    fn test_defaults_and_quit() {
+        let previous = Conditions { month: 8, temperature_c: Some(30.0), ..starting_conditions() };
+        let (result, _) = run("\n\n\n\n\n", &previous);
+        let query = result.unwrap();
+        assert_eq!(query.month, 8);
+        assert_eq!(query.weather, Weather::Sunny);
+        assert_eq!(query.temperature_c, Some(30.0));
+
+        assert!(run("7\nq\n", &previous).0.is_none());
+        assert!(run("7\n", &previous).0.is_none());
+    }
+}
@@ -1,133 +1,644 @@
 //This module implements algorithms to predict bike rental count given weather conditions
-use crate::common::{DaySummary, Conditions};
-use std::collections::{HashMap, HashSet, VecDeque};
+use crate::common::{DaySummary, Conditions, Weather, TemperatureCategory, PrecipitationIntensity};
+#[cfg(test)]
+use crate::common::test_day;
+use crate::model::day_number;
+use crate::similarity::{category_score, conditions_of, measured_score, similarity_score, SimilarityConfig};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
 
+//This struct is the graph where days with the same weather, temperature and precipitation are all connected to each other
+//Every such class is a clique, so instead of storing all of its edges only the members of each class are kept
 #[derive(Debug)]
 pub struct Graph {
-    pub adj_list: HashMap<usize, Vec<usize>>,
+    pub classes: Vec<Vec<usize>>,
+    pub class_of: Vec<usize>,
 }
 
-// This functions checks if two days are similar enough to connect in the graph
-fn are_similar(a: &DaySummary, b: &DaySummary) -> bool {
-    a.weather == b.weather &&
-    a.temperature_category == b.temperature_category &&
-    a.precipitation == b.precipitation
+// This function gives the key two days need to share to be connected in the graph
+fn class_key(day: &DaySummary) -> (Weather, TemperatureCategory, PrecipitationIntensity) {
+    (day.weather.clone(), day.temperature_category.clone(), day.precipitation.clone())
 }
 
-// This function builds the graph by connecting similar nodes
+// This function builds the graph by putting every day into the class of its key, which takes linear time and memory
 pub fn build_graph(days: &[DaySummary]) -> Graph {
-    let mut adj_list: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut class_ids: HashMap<(Weather, TemperatureCategory, PrecipitationIntensity), usize> = HashMap::new();
+    let mut classes: Vec<Vec<usize>> = Vec::new();
+    let mut class_of = Vec::with_capacity(days.len());
+    for (i, day) in days.iter().enumerate() {
+        let id = *class_ids.entry(class_key(day)).or_insert_with(|| {
+            classes.push(Vec::new());
+            classes.len() - 1
+        });
+        classes[id].push(i);
+        class_of.push(id);
+    }
+    Graph { classes, class_of }
+}
+
+//This trait lets the traversals run on either graph, since they only need the neighbours of a node
+pub trait Adjacency {
+    fn neighbors(&self, node: usize) -> impl Iterator<Item = usize> + '_;
+
+    //Graphs made of cliques can return the clique of a node, so a traversal only has to expand each clique once
+    fn clique_of(&self, _node: usize) -> Option<usize> {
+        None
+    }
+}
+
+impl Adjacency for Graph {
+    fn neighbors(&self, node: usize) -> impl Iterator<Item = usize> + '_ {
+        self.class_of
+            .get(node)
+            .map(|&id| self.classes[id].as_slice())
+            .unwrap_or(&[])
+            .iter()
+            .copied()
+            .filter(move |&other| other != node)
+    }
+
+    fn clique_of(&self, node: usize) -> Option<usize> {
+        self.class_of.get(node).copied()
+    }
+}
+
+//An edge of the weighted graph. The distance is the similarity score between the two days, so 0 means identical
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edge {
+    pub node: usize,
+    pub distance: f64,
+}
+
+impl Edge {
+    //Turns the distance into a similarity between 0 and 1 for algorithms where heavier edges mean closer days
+    pub fn similarity(&self) -> f64 {
+        1.0 / (1.0 + self.distance)
+    }
+}
+
+//This struct is the weighted version of Graph where every edge keeps how far apart its two days are
+//Edges are stored in both directions
+#[derive(Debug)]
+pub struct WeightedGraph {
+    pub adj_list: HashMap<usize, Vec<Edge>>,
+}
+
+impl Adjacency for WeightedGraph {
+    fn neighbors(&self, node: usize) -> impl Iterator<Item = usize> + '_ {
+        self.adj_list.get(&node).into_iter().flatten().map(|edge| edge.node)
+    }
+}
+
+//This enum chooses how the weighted graph decides which days to connect
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EdgeRule {
+    //connect every day to its k closest days (and those days back to it)
+    Nearest(usize),
+    //connect every pair of days that are at most this far apart
+    Threshold(f64),
+}
+
+// This function builds the weighted graph from the day distances
+// Days are first grouped into buckets of the same month and categories, since the category part of the distance is then fixed per pair of buckets
+// Buckets are visited from closest to furthest and the search stops once a bucket cannot hold anything closer than what was already found,
+// which avoids comparing every pair of days unless every pair is actually needed
+// Within a bucket the days are sorted by the measured value with the larger weight, and the scan walks outwards from the day's own value:
+// that value alone puts a floor under the distance, so the scan stops as soon as the floor passes the bound instead of comparing every pair.
+// When both measured weights are zero every day in a bucket is the same distance away and the whole bucket is still scanned
+pub fn build_weighted_graph(days: &[DaySummary], similarity: &SimilarityConfig, rule: EdgeRule) -> WeightedGraph {
+    let mut bucket_ids: HashMap<(u32, Weather, TemperatureCategory, PrecipitationIntensity), usize> = HashMap::new();
+    let mut buckets: Vec<Vec<usize>> = Vec::new();
+    let mut bucket_of = Vec::with_capacity(days.len());
+    for (i, day) in days.iter().enumerate() {
+        let (weather, temperature, precipitation) = class_key(day);
+        let id = *bucket_ids.entry((day.month, weather, temperature, precipitation)).or_insert_with(|| {
+            buckets.push(Vec::new());
+            buckets.len() - 1
+        });
+        buckets[id].push(i);
+        bucket_of.push(id);
+    }
+
+    //the sort key is the measured value with the larger weight, and floor() is the part of measured_score that comes from it
+    let (key_weight, key_range, key): (f64, f64, fn(&DaySummary) -> f64) = if similarity.temperature_c >= similarity.rainfall_mm {
+        (similarity.temperature_c, similarity.temperature_c_range, |day| day.avg_temperature)
+    } else {
+        (similarity.rainfall_mm, similarity.rainfall_mm_range, |day| day.total_rainfall)
+    };
+    let floor = |a: f64, b: f64| key_weight * ((a - b).abs() / key_range).min(1.0);
+    for bucket in buckets.iter_mut() {
+        bucket.sort_by(|&a, &b| key(&days[a]).total_cmp(&key(&days[b])).then(a.cmp(&b)));
+    }
+
+    let queries: Vec<Conditions> = days.iter().map(conditions_of).collect();
+    let bucket_distance: Vec<Vec<f64>> = buckets
+        .iter()
+        .map(|a| buckets.iter().map(|b| category_score(&days[a[0]], &queries[b[0]], similarity)).collect())
+        .collect();
+    let bucket_order: Vec<Vec<usize>> = bucket_distance
+        .iter()
+        .map(|row| {
+            let mut order: Vec<usize> = (0..row.len()).collect();
+            order.sort_by(|&a, &b| row[a].total_cmp(&row[b]));
+            order
+        })
+        .collect();
+
+    let mut edges: HashMap<(usize, usize), f64> = HashMap::new();
     for (i, day_i) in days.iter().enumerate() {
-        for (j, day_j) in days.iter().enumerate() {
-            if i != j && are_similar(day_i, day_j) {
-                adj_list.entry(i).or_default().push(j);
+        let own = bucket_of[i];
+        let value = key(day_i);
+        //for the nearest rule this is kept sorted by distance and then index, so ties go to the lower index as a sort over all days would give
+        let mut distances: Vec<(usize, f64)> = Vec::new();
+        let bound = |distances: &Vec<(usize, f64)>| match rule {
+            EdgeRule::Threshold(epsilon) => epsilon,
+            EdgeRule::Nearest(0) => f64::NEG_INFINITY,
+            EdgeRule::Nearest(k) if distances.len() >= k => distances[k - 1].1,
+            EdgeRule::Nearest(_) => f64::INFINITY,
+        };
+        for &bucket in &bucket_order[own] {
+            let base = bucket_distance[own][bucket];
+            //the measured part is never negative so nothing in this or any later bucket can beat the bound
+            if base > bound(&distances) {
+                break;
             }
+            let members = &buckets[bucket];
+            let split = members.partition_point(|&j| key(&days[j]) < value);
+            let (mut below, mut above) = (split, split);
+            loop {
+                //take whichever neighbour in the sorted bucket is closer in value, so the floor never goes down during the scan
+                let j = match (below.checked_sub(1), members.get(above)) {
+                    (Some(lower), Some(&upper)) if value - key(&days[members[lower]]) <= key(&days[upper]) - value => {
+                        below = lower;
+                        members[lower]
+                    }
+                    (_, Some(&upper)) => {
+                        above += 1;
+                        upper
+                    }
+                    (Some(lower), None) => {
+                        below = lower;
+                        members[lower]
+                    }
+                    (None, None) => break,
+                };
+                if base + floor(value, key(&days[j])) > bound(&distances) {
+                    break;
+                }
+                if j == i {
+                    continue;
+                }
+                let distance = base + measured_score(day_i, &queries[j], similarity);
+                if distance > bound(&distances) {
+                    continue;
+                }
+                match rule {
+                    EdgeRule::Threshold(_) => distances.push((j, distance)),
+                    EdgeRule::Nearest(k) => {
+                        let at = distances.partition_point(|&(other, d)| d.total_cmp(&distance).then(other.cmp(&j)).is_lt());
+                        distances.insert(at, (j, distance));
+                        distances.truncate(k);
+                    }
+                }
+            }
+        }
+        //the smaller index goes first so an edge found from both ends is only stored once
+        for (j, distance) in distances {
+            edges.insert((i.min(j), i.max(j)), distance);
         }
     }
-    Graph { adj_list }
-}
 
-//The function for calculating similarity score is outlined. If the query and the day have essentially different values, the score goes up
-//The higher the score is, the more that value is dissimilar from what we're interested in
-fn similarity_score(day: &DaySummary, query: &Conditions) -> i32 {
-    let mut score = 0;
-    if day.month != query.month {
-        score += 10;
-    }
-    if day.weather != query.weather {
-        score += 30;
+    let mut adj_list: HashMap<usize, Vec<Edge>> = HashMap::new();
+    let mut sorted: Vec<((usize, usize), f64)> = edges.into_iter().collect();
+    sorted.sort_by_key(|&(pair, _)| pair);
+    for ((a, b), distance) in sorted {
+        adj_list.entry(a).or_default().push(Edge { node: b, distance });
+        adj_list.entry(b).or_default().push(Edge { node: a, distance });
     }
-    if day.temperature_category != query.temperature {
-        score += 20;
+    WeightedGraph { adj_list }
+}
+
+//This enum decides which days a search is allowed to look at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scope {
+    //Only the days in the same connected component as the start day
+    Component(usize),
+    //Every day, by starting a new traversal from each day that has not been visited yet
+    Global,
+    //The neighbourhood around the days that match the query best
+    Anchored(Anchor),
+}
+
+//This struct controls an anchored search: how many best-scoring seed days to start from,
+//how many edges away from a seed to go, and optionally the worst score a day may have to be expanded into
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Anchor {
+    pub seeds: usize,
+    pub max_depth: usize,
+    pub max_score: Option<f64>,
+}
+
+impl Default for Anchor {
+    fn default() -> Self {
+        Anchor {
+            seeds: 1,
+            max_depth: 2,
+            max_score: None,
+        }
     }
-    if day.precipitation != query.precipitation {
-        score += 15;
+}
+
+//This struct keeps days out of what a search can return, so a past day can be estimated the way it could have been at the time
+//Dates are day numbers (see model::day_number); excluded days are still walked through, they just cannot be picked or be seeds
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Exclusion {
+    //the day being estimated and how many days on each side of it are left out with it (0 leaves out only the day)
+    pub around: Option<(i64, i64)>,
+    //only days strictly before this one can be picked
+    pub before: Option<i64>,
+}
+
+impl Exclusion {
+    pub fn allows(&self, day: &DaySummary) -> bool {
+        if *self == Exclusion::default() {
+            return true;
+        }
+        //a date that cannot be read cannot be compared, so the day is left out to be safe
+        let Some(number) = day_number(&day.date) else {
+            return false;
+        };
+        self.around.is_none_or(|(date, window)| (number - date).abs() > window) && self.before.is_none_or(|cutoff| number < cutoff)
     }
-    score
 }
 
-// This function utilizes bfs algorithm, and we look for the min similarity score after iteration
-pub fn bfs_closest(start: usize, graph: &Graph, days: &[DaySummary], query: &Conditions) -> Option<DaySummary> {
-    let mut visited = HashSet::new();
-    let mut queue = VecDeque::new();
-    let mut best_match = None;
-    let mut best_score = i32::MAX;
+//This function drops the excluded days from the visiting order
+fn allowed(order: Vec<usize>, days: &[DaySummary], exclusion: &Exclusion) -> Vec<usize> {
+    order.into_iter().filter(|&node| exclusion.allows(&days[node])).collect()
+}
+
+//This struct is the result of a search: the best day, its score and how many days were compared to the query
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub day: DaySummary,
+    pub score: f64,
+    pub examined: usize,
+}
 
-    queue.push_back(start);
-    visited.insert(start);
+// This function picks the seeds for an anchored search: the days with the lowest score that may be picked, earliest index first on ties
+fn best_seeds(days: &[DaySummary], query: &Conditions, similarity: &SimilarityConfig, exclusion: &Exclusion, count: usize) -> Vec<usize> {
+    let mut scored: Vec<(usize, f64)> = days
+        .iter()
+        .enumerate()
+        .filter(|(_, day)| exclusion.allows(day))
+        .map(|(i, day)| (i, similarity_score(day, query, similarity)))
+        .collect();
+    scored.sort_by(|a, b| a.1.total_cmp(&b.1));
+    scored.into_iter().take(count).map(|(i, _)| i).collect()
+}
+
+// This function lists the nodes in scope in the order they are visited
+// The frontier is used as a queue for bfs and as a stack for dfs, and for the global scope each unvisited node becomes a new source
+// For the anchored scope the traversal stops at max_depth and does not step onto days scoring worse than max_score
+fn visit_order<G: Adjacency>(scope: Scope, graph: &G, days: &[DaySummary], query: &Conditions, similarity: &SimilarityConfig, exclusion: &Exclusion, depth_first: bool) -> Vec<usize> {
+    let (sources, max_depth, max_score) = match scope {
+        Scope::Component(start) if start < days.len() => (vec![start], usize::MAX, None),
+        Scope::Component(_) => (Vec::new(), 0, None),
+        Scope::Global => ((0..days.len()).collect(), usize::MAX, None),
+        Scope::Anchored(anchor) => (best_seeds(days, query, similarity, exclusion, anchor.seeds), anchor.max_depth, anchor.max_score),
+    };
 
-    while let Some(node) = queue.pop_front() {
-        let day = &days[node];
-        let score = similarity_score(day, query);
+    //A day is listed when it is first taken off the frontier, but with a depth limit it is expanded again whenever a shorter way to it turns up,
+    //since dfs can reach a day through a long path first and would otherwise cut off what lies behind it
+    let depth_limited = max_depth != usize::MAX;
+    let mut best_depth: HashMap<usize, usize> = HashMap::new();
+    let mut listed = HashSet::new();
+    let mut expanded_cliques: HashMap<usize, usize> = HashMap::new();
+    let mut frontier = VecDeque::new();
+    let mut order = Vec::new();
 
-        if score < best_score {
-            best_score = score;
-            best_match = Some(day.clone());
+    for source in sources {
+        if best_depth.get(&source).is_some_and(|&known| !depth_limited || known == 0) {
+            continue;
         }
+        best_depth.insert(source, 0);
+        frontier.push_back((source, 0));
 
-        if let Some(neighbors) = graph.adj_list.get(&node) {
-            for &neighbor in neighbors {
-                if visited.insert(neighbor) {
-                    queue.push_back(neighbor);
+        while let Some((node, depth)) = if depth_first { frontier.pop_back() } else { frontier.pop_front() } {
+            //a shorter way to this day was found after this entry was added
+            if best_depth.get(&node).is_some_and(|&known| known < depth) {
+                continue;
+            }
+            if listed.insert(node) {
+                order.push(node);
+            }
+            if depth >= max_depth {
+                continue;
+            }
+            //every neighbour of a clique member was already queued when the first member of the clique was expanded at this depth or less
+            if let Some(clique) = graph.clique_of(node) {
+                if expanded_cliques.get(&clique).is_some_and(|&expanded| expanded <= depth) {
+                    continue;
+                }
+                expanded_cliques.insert(clique, depth);
+            }
+            for neighbor in graph.neighbors(node) {
+                if max_score.is_some_and(|bound| similarity_score(&days[neighbor], query, similarity) > bound) {
+                    continue;
+                }
+                let shorter = match best_depth.get(&neighbor) {
+                    None => true,
+                    Some(&known) => depth_limited && depth + 1 < known,
+                };
+                if shorter {
+                    best_depth.insert(neighbor, depth + 1);
+                    frontier.push_back((neighbor, depth + 1));
                 }
             }
         }
     }
+    order
+}
 
-    best_match
+//This function goes through the visited nodes and keeps the first one with the lowest similarity score
+fn closest_in(order: &[usize], days: &[DaySummary], query: &Conditions, similarity: &SimilarityConfig) -> Option<SearchResult> {
+    let mut best: Option<SearchResult> = None;
+    for &node in order {
+        let score = similarity_score(&days[node], query, similarity);
+        if best.as_ref().is_none_or(|b| score < b.score) {
+            best = Some(SearchResult { day: days[node].clone(), score, examined: 0 });
+        }
+    }
+    best.map(|b| SearchResult { examined: order.len(), ..b })
+}
+
+// This function utilizes bfs algorithm, and we look for the min similarity score after iteration
+pub fn bfs_closest<G: Adjacency>(scope: Scope, graph: &G, days: &[DaySummary], query: &Conditions, similarity: &SimilarityConfig, exclusion: &Exclusion) -> Option<SearchResult> {
+    closest_in(&allowed(visit_order(scope, graph, days, query, similarity, exclusion, false), days, exclusion), days, query, similarity)
 }
 
 // Same thing with finding the min by key is applied for dfs algorithm, but in the reverse order
-pub fn dfs_closest(start: usize, graph: &Graph, days: &[DaySummary], query: &Conditions) -> Option<DaySummary> {
-    let mut visited = HashSet::new();
-    let mut stack = Vec::new();
-    let mut best_match = None;
-    let mut best_score = i32::MAX;
+pub fn dfs_closest<G: Adjacency>(scope: Scope, graph: &G, days: &[DaySummary], query: &Conditions, similarity: &SimilarityConfig, exclusion: &Exclusion) -> Option<SearchResult> {
+    closest_in(&allowed(visit_order(scope, graph, days, query, similarity, exclusion, true), days, exclusion), days, query, similarity)
+}
 
-    stack.push(start);
-    visited.insert(start);
+//This enum chooses how much each of the k nearest days counts towards the estimate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Weighting {
+    Uniform,
+    InverseScore,
+}
+
+impl Weighting {
+    //This function gives how much a day with this similarity score counts
+    pub fn weight(&self, score: f64) -> f64 {
+        match self {
+            Weighting::Uniform => 1.0,
+            Weighting::InverseScore => 1.0 / (1.0 + score),
+        }
+    }
+}
 
-    while let Some(node) = stack.pop() {
-        let day = &days[node];
-        let score = similarity_score(day, query);
+//This struct holds the settings for predict: how many neighbours, how to weight them and the interval coverage (0.8 = 10th to 90th percentile)
+#[derive(Debug, Clone)]
+pub struct PredictConfig {
+    pub k: usize,
+    pub weighting: Weighting,
+    pub coverage: f64,
+}
 
-        if score < best_score {
-            best_score = score;
-            best_match = Some(day.clone());
+impl Default for PredictConfig {
+    fn default() -> Self {
+        PredictConfig {
+            k: 5,
+            weighting: Weighting::InverseScore,
+            coverage: 0.8,
         }
+    }
+}
 
-        if let Some(neighbors) = graph.adj_list.get(&node) {
-            for &neighbor in neighbors {
-                if visited.insert(neighbor) {
-                    stack.push(neighbor);
-                }
+//One of the k nearest days together with its similarity score and the weight it got
+#[derive(Debug, Clone)]
+pub struct Neighbour {
+    pub day: DaySummary,
+    pub score: f64,
+    pub weight: f64,
+}
+
+//This struct is the output of predict with the weighted estimates and the prediction interval
+#[derive(Debug, Clone)]
+pub struct Prediction {
+    pub neighbours: Vec<Neighbour>,
+    pub mean: f64,
+    pub median: f64,
+    pub lower: f64,
+    pub upper: f64,
+    pub examined: usize,
+}
+
+//This function finds the value below which the given fraction of the total weight lies
+//The values have to be sorted in ascending order
+fn weighted_quantile(sorted: &[(f64, f64)], q: f64) -> f64 {
+    let total: f64 = sorted.iter().map(|(_, w)| w).sum();
+    let target = q * total;
+    let mut cumulative = 0.0;
+    for &(value, weight) in sorted {
+        cumulative += weight;
+        if cumulative >= target {
+            return value;
+        }
+    }
+    sorted.last().map(|(v, _)| *v).unwrap_or(0.0)
+}
+
+//This function takes the k days in scope with the lowest similarity score and combines their rentals into an estimate
+//Ties are broken by bfs order so the result is the same as bfs_closest when k is 1
+pub fn predict<G: Adjacency>(
+    scope: Scope,
+    graph: &G,
+    days: &[DaySummary],
+    query: &Conditions,
+    similarity: &SimilarityConfig,
+    config: &PredictConfig,
+    exclusion: &Exclusion,
+) -> Option<Prediction> {
+    let order = allowed(visit_order(scope, graph, days, query, similarity, exclusion, false), days, exclusion);
+    if config.k == 0 || order.is_empty() {
+        return None;
+    }
+    let examined = order.len();
+
+    let mut candidates: Vec<(usize, f64)> = order
+        .into_iter()
+        .map(|node| (node, similarity_score(&days[node], query, similarity)))
+        .collect();
+    //sort_by is stable so equal scores keep their bfs order
+    candidates.sort_by(|a, b| a.1.total_cmp(&b.1));
+    candidates.truncate(config.k);
+
+    let neighbours: Vec<Neighbour> = candidates
+        .into_iter()
+        .map(|(node, score)| Neighbour {
+            day: days[node].clone(),
+            score,
+            weight: config.weighting.weight(score),
+        })
+        .collect();
+
+    let total_weight: f64 = neighbours.iter().map(|n| n.weight).sum();
+    let mean = neighbours.iter().map(|n| n.weight * n.day.total_rentals as f64).sum::<f64>() / total_weight;
+
+    let mut sorted: Vec<(f64, f64)> = neighbours.iter().map(|n| (n.day.total_rentals as f64, n.weight)).collect();
+    sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let tail = (1.0 - config.coverage.clamp(0.0, 1.0)) / 2.0;
+
+    Some(Prediction {
+        mean,
+        median: weighted_quantile(&sorted, 0.5),
+        lower: weighted_quantile(&sorted, tail),
+        upper: weighted_quantile(&sorted, 1.0 - tail),
+        neighbours,
+        examined,
+    })
+}
+
+//One day on a path together with the distance travelled from the first day to reach it
+#[derive(Debug, Clone)]
+pub struct PathStep {
+    pub day: DaySummary,
+    pub cumulative: f64,
+}
+
+//This struct is a shortest path between two days, with how many nodes were taken off the queue to find it
+#[derive(Debug, Clone)]
+pub struct DayPath {
+    pub steps: Vec<PathStep>,
+    pub total_distance: f64,
+    pub expanded: usize,
+}
+
+//This struct is an entry of the priority queue. The ordering is reversed so BinaryHeap pops the lowest estimate first
+#[derive(Debug, PartialEq)]
+struct QueueEntry {
+    estimate: f64,
+    node: usize,
+}
+
+impl Eq for QueueEntry {}
+
+impl Ord for QueueEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate).then(other.node.cmp(&self.node))
+    }
+}
+
+impl PartialOrd for QueueEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+//This function finds the index of a day from its date, written like the dataset (dd/mm/yyyy)
+pub fn find_day(days: &[DaySummary], date: &str) -> Option<usize> {
+    days.iter().position(|day| day.date == date)
+}
+
+// This function is the shared search behind dijkstra_path and astar_path
+// remaining gives a lower bound on the distance left from a node to the goal, which is 0 everywhere for dijkstra
+fn best_path(graph: &WeightedGraph, days: &[DaySummary], from: usize, to: usize, remaining: impl Fn(usize) -> f64) -> Option<DayPath> {
+    if from >= days.len() || to >= days.len() {
+        return None;
+    }
+    let mut distance: HashMap<usize, f64> = HashMap::new();
+    let mut previous: HashMap<usize, usize> = HashMap::new();
+    let mut done = HashSet::new();
+    let mut heap = BinaryHeap::new();
+    let mut expanded = 0;
+
+    distance.insert(from, 0.0);
+    heap.push(QueueEntry { estimate: remaining(from), node: from });
+
+    while let Some(QueueEntry { node, .. }) = heap.pop() {
+        if !done.insert(node) {
+            continue;
+        }
+        expanded += 1;
+        if node == to {
+            break;
+        }
+        let travelled = distance[&node];
+        for edge in graph.adj_list.get(&node).into_iter().flatten() {
+            let candidate = travelled + edge.distance;
+            if distance.get(&edge.node).is_none_or(|&known| candidate < known) {
+                distance.insert(edge.node, candidate);
+                previous.insert(edge.node, node);
+                heap.push(QueueEntry { estimate: candidate + remaining(edge.node), node: edge.node });
             }
         }
     }
 
-    best_match
+    let total_distance = *distance.get(&to)?;
+    let mut chain = vec![to];
+    while let Some(&node) = previous.get(chain.last()?) {
+        chain.push(node);
+    }
+    chain.reverse();
+
+    Some(DayPath {
+        steps: chain
+            .into_iter()
+            .map(|node| PathStep { day: days[node].clone(), cumulative: distance[&node] })
+            .collect(),
+        total_distance,
+        expanded,
+    })
+}
+
+// This function finds the chain of similar days with the lowest total distance using dijkstra's algorithm
+pub fn dijkstra_path(graph: &WeightedGraph, days: &[DaySummary], from: usize, to: usize) -> Option<DayPath> {
+    best_path(graph, days, from, to, |_| 0.0)
 }
 
+// Same as dijkstra_path but guided by A*, which usually expands far fewer days
+// The direct distance to the goal is the heuristic: every part of the score obeys the triangle inequality,
+// so no path can be shorter than it. The similarity config has to be the one the graph was built with
+pub fn astar_path(graph: &WeightedGraph, days: &[DaySummary], from: usize, to: usize, similarity: &SimilarityConfig) -> Option<DayPath> {
+    let goal = conditions_of(days.get(to)?);
+    best_path(graph, days, from, to, |node| similarity_score(&days[node], &goal, similarity))
+}
+
+// This function counts the fewest edges between two days with bfs, ignoring edge weights
+pub fn degrees_of_separation<G: Adjacency>(graph: &G, from: usize, to: usize) -> Option<usize> {
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::new();
+    visited.insert(from);
+    queue.push_back((from, 0));
+
+    while let Some((node, hops)) = queue.pop_front() {
+        if node == to {
+            return Some(hops);
+        }
+        for neighbor in graph.neighbors(node) {
+            if visited.insert(neighbor) {
+                queue.push_back((neighbor, hops + 1));
+            }
+        }
+    }
+    None
+}
 
 #[test]
 //The test aims to see if given two day summaries, the query correctly identifies the one that is closer
 fn test_bfs_finds_matching_day() {
     let graph_data = vec![
         DaySummary {
-            date: "01/01/2018".to_string(),
-            total_rentals: 400,
-            month: 1,
-            weather: crate::common::Weather::Sunny,
             temperature_category: crate::common::TemperatureCategory::Cold,
-            precipitation: crate::common::PrecipitationIntensity::None,
+            avg_temperature: 0.0,
+            ..test_day("01/01/2018", 400)
         },
         DaySummary {
-            date: "02/01/2018".to_string(),
-            total_rentals: 500,
-            month: 1,
-            weather: crate::common::Weather::Sunny,
             temperature_category: crate::common::TemperatureCategory::Cold,
-            precipitation: crate::common::PrecipitationIntensity::None,
+            avg_temperature: 0.0,
+            ..test_day("02/01/2018", 500)
         }
     ];
 
@@ -136,10 +647,331 @@ fn test_bfs_finds_matching_day() {
         weather: crate::common::Weather::Sunny,
         temperature: crate::common::TemperatureCategory::Cold,
         precipitation: crate::common::PrecipitationIntensity::None,
+        temperature_c: None,
+        rainfall_mm: None,
     };
 
     let graph = build_graph(&graph_data);
-    let result = bfs_closest(0, &graph, &graph_data, &query);
+    let result = bfs_closest(Scope::Component(0), &graph, &graph_data, &query, &SimilarityConfig::default(), &Exclusion::default());
     assert!(result.is_some());
-    assert_eq!(result.unwrap().total_rentals, 400);
+    assert_eq!(result.unwrap().day.total_rentals, 400);
+}
+
+#[test]
+//The test checks that predict keeps the k closest days and that uniform weighting gives the plain mean
+fn test_predict_weighted_neighbours() {
+    let make_day = |date: &str, total_rentals: i32, weather: crate::common::Weather| DaySummary {
+        weather,
+        temperature_category: crate::common::TemperatureCategory::Cold,
+        avg_temperature: 0.0,
+        ..test_day(date, total_rentals)
+    };
+    let days = vec![
+        make_day("01/01/2018", 400, crate::common::Weather::Sunny),
+        make_day("02/01/2018", 600, crate::common::Weather::Sunny),
+        make_day("03/01/2018", 800, crate::common::Weather::Sunny),
+    ];
+    let query = Conditions {
+        month: 1,
+        weather: crate::common::Weather::Sunny,
+        temperature: crate::common::TemperatureCategory::Cold,
+        precipitation: crate::common::PrecipitationIntensity::None,
+        temperature_c: None,
+        rainfall_mm: None,
+    };
+    let graph = build_graph(&days);
+
+    let config = PredictConfig { k: 2, weighting: Weighting::Uniform, coverage: 1.0 };
+    let prediction = predict(Scope::Component(0), &graph, &days, &query, &SimilarityConfig::default(), &config, &Exclusion::default()).unwrap();
+    assert_eq!(prediction.neighbours.len(), 2);
+    assert_eq!(prediction.mean, 500.0);
+    assert_eq!(prediction.lower, 400.0);
+    assert_eq!(prediction.upper, 600.0);
+
+    let config = PredictConfig { k: 0, ..PredictConfig::default() };
+    assert!(predict(Scope::Component(0), &graph, &days, &query, &SimilarityConfig::default(), &config, &Exclusion::default()).is_none());
+}
+
+#[test]
+//The test checks that excluded days, a window around them and days after the cutoff are never picked
+fn test_exclusion_leaves_out_days() {
+    let make_day = |date: &str, total_rentals: i32| DaySummary {
+        temperature_category: crate::common::TemperatureCategory::Cold,
+        avg_temperature: 0.0,
+        ..test_day(date, total_rentals)
+    };
+    let days = vec![make_day("01/01/2018", 400), make_day("02/01/2018", 600), make_day("03/01/2018", 800)];
+    let query = Conditions {
+        month: 1,
+        weather: crate::common::Weather::Sunny,
+        temperature: crate::common::TemperatureCategory::Cold,
+        precipitation: crate::common::PrecipitationIntensity::None,
+        temperature_c: None,
+        rainfall_mm: None,
+    };
+    let graph = build_graph(&days);
+    let similarity = SimilarityConfig::default();
+    let first = day_number("01/01/2018");
+
+    let closest = |exclusion: Exclusion| bfs_closest(Scope::Global, &graph, &days, &query, &similarity, &exclusion).map(|r| (r.day.total_rentals, r.examined));
+    assert_eq!(closest(Exclusion::default()), Some((400, 3)));
+    assert_eq!(closest(Exclusion { around: first.map(|d| (d, 0)), before: None }), Some((600, 2)));
+    assert_eq!(closest(Exclusion { around: first.map(|d| (d, 1)), before: None }), Some((800, 1)));
+    assert_eq!(closest(Exclusion { around: None, before: first }), None);
+
+    //with one seed and no steps the anchored search only sees its seed, which has to be a day that may be picked
+    let seed_only = Scope::Anchored(Anchor { seeds: 1, max_depth: 0, max_score: None });
+    let anchored = |exclusion: Exclusion| bfs_closest(seed_only, &graph, &days, &query, &similarity, &exclusion).map(|r| (r.day.total_rentals, r.examined));
+    assert_eq!(anchored(Exclusion::default()), Some((400, 1)));
+    assert_eq!(anchored(Exclusion { around: first.map(|d| (d, 0)), before: None }), Some((600, 1)));
+    assert_eq!(anchored(Exclusion { around: first.map(|d| (d, 1)), before: None }), Some((800, 1)));
+
+    let config = PredictConfig { k: 3, weighting: Weighting::Uniform, coverage: 1.0 };
+    let before_third = Exclusion { around: None, before: day_number("03/01/2018") };
+    let prediction = predict(Scope::Global, &graph, &days, &query, &similarity, &config, &before_third).unwrap();
+    assert_eq!(prediction.neighbours.len(), 2);
+    assert_eq!(prediction.mean, 500.0);
+}
+
+#[test]
+//The test checks that a depth-limited dfs still reaches a day behind one it first found through a longer path
+fn test_depth_limited_dfs_uses_shortest_depth() {
+    let make_day = |date: &str, total_rentals: i32| DaySummary {
+        month: 7,
+        temperature_category: crate::common::TemperatureCategory::Hot,
+        avg_temperature: 0.0,
+        ..test_day(date, total_rentals)
+    };
+    //S=0, A=1, B=2, C=3, D=4, E=5 with the edges S-A, A-D, D-E, S-B, B-C, C-D
+    let days: Vec<DaySummary> = (0..6).map(|i| make_day(&format!("0{}/07/2018", i + 1), 100 * i)).collect();
+    let mut adj_list: HashMap<usize, Vec<Edge>> = HashMap::new();
+    for (a, b) in [(0, 1), (1, 4), (4, 5), (0, 2), (2, 3), (3, 4)] {
+        adj_list.entry(a).or_default().push(Edge { node: b, distance: 1.0 });
+        adj_list.entry(b).or_default().push(Edge { node: a, distance: 1.0 });
+    }
+    let graph = WeightedGraph { adj_list };
+    let query = conditions_of(&days[0]);
+    let similarity = SimilarityConfig::default();
+    //dfs goes S-B-C-D first, which puts D at depth 3, yet S-A-D-E keeps E within depth 3
+    let order = visit_order(Scope::Anchored(Anchor { seeds: 1, max_depth: 3, max_score: None }), &graph, &days, &query, &similarity, &Exclusion::default(), true);
+    let mut sorted = order.clone();
+    sorted.sort();
+    assert_eq!(sorted, [0, 1, 2, 3, 4, 5]);
+    let shallow = visit_order(Scope::Anchored(Anchor { seeds: 1, max_depth: 2, max_score: None }), &graph, &days, &query, &similarity, &Exclusion::default(), true);
+    assert_eq!(shallow.len(), 5);
+}
+
+#[test]
+//The test checks that the global scope finds an exact match in another component while the component scope cannot
+fn test_global_scope_crosses_components() {
+    let make_day = |date: &str, total_rentals: i32, weather: crate::common::Weather| DaySummary {
+        month: 7,
+        weather,
+        temperature_category: crate::common::TemperatureCategory::Hot,
+        avg_temperature: 0.0,
+        ..test_day(date, total_rentals)
+    };
+    let days = vec![
+        make_day("01/07/2018", 300, crate::common::Weather::Foggy),
+        make_day("02/07/2018", 900, crate::common::Weather::Sunny),
+        make_day("03/07/2018", 950, crate::common::Weather::Sunny),
+    ];
+    let query = Conditions {
+        month: 7,
+        weather: crate::common::Weather::Sunny,
+        temperature: crate::common::TemperatureCategory::Hot,
+        precipitation: crate::common::PrecipitationIntensity::None,
+        temperature_c: None,
+        rainfall_mm: None,
+    };
+    let graph = build_graph(&days);
+
+    let local = bfs_closest(Scope::Component(0), &graph, &days, &query, &SimilarityConfig::default(), &Exclusion::default()).unwrap();
+    assert_eq!(local.day.total_rentals, 300);
+    assert_eq!(local.examined, 1);
+
+    let global = dfs_closest(Scope::Global, &graph, &days, &query, &SimilarityConfig::default(), &Exclusion::default()).unwrap();
+    assert_eq!(global.score, 0.0);
+    assert_eq!(global.examined, 3);
+}
+
+#[test]
+//The test checks that an anchored search starts at the best seed and respects the depth and score bounds
+fn test_anchored_scope_limits() {
+    let make_day = |date: &str, month: u32, weather: crate::common::Weather| DaySummary {
+        month,
+        weather,
+        temperature_category: crate::common::TemperatureCategory::Hot,
+        avg_temperature: 0.0,
+        ..test_day(date, 1000)
+    };
+    let days = vec![
+        make_day("01/06/2018", 6, crate::common::Weather::Foggy),
+        make_day("01/07/2018", 7, crate::common::Weather::Sunny),
+        make_day("01/08/2018", 8, crate::common::Weather::Sunny),
+        make_day("02/08/2018", 8, crate::common::Weather::Sunny),
+    ];
+    let query = Conditions {
+        month: 7,
+        weather: crate::common::Weather::Sunny,
+        temperature: crate::common::TemperatureCategory::Hot,
+        precipitation: crate::common::PrecipitationIntensity::None,
+        temperature_c: None,
+        rainfall_mm: None,
+    };
+    let graph = build_graph(&days);
+
+    let seed_only = Anchor { seeds: 1, max_depth: 0, max_score: None };
+    let result = bfs_closest(Scope::Anchored(seed_only), &graph, &days, &query, &SimilarityConfig::default(), &Exclusion::default()).unwrap();
+    assert_eq!(result.day.date, "01/07/2018");
+    assert_eq!(result.examined, 1);
+
+    let neighbourhood = Anchor { seeds: 1, max_depth: 1, max_score: None };
+    assert_eq!(bfs_closest(Scope::Anchored(neighbourhood), &graph, &days, &query, &SimilarityConfig::default(), &Exclusion::default()).unwrap().examined, 3);
+
+    let bounded = Anchor { seeds: 1, max_depth: 1, max_score: Some(0.0) };
+    assert_eq!(bfs_closest(Scope::Anchored(bounded), &graph, &days, &query, &SimilarityConfig::default(), &Exclusion::default()).unwrap().examined, 1);
+}
+
+#[test]
+//The test checks that the weighted graph stores symmetric edges and that the k-nearest and threshold rules connect the right days
+fn test_weighted_graph_rules() {
+    let make_day = |date: &str, month: u32| DaySummary {
+        month,
+        avg_temperature: 15.0,
+        ..test_day(date, 1000)
+    };
+    let days = vec![make_day("01/01/2018", 1), make_day("01/02/2018", 2), make_day("01/07/2018", 7)];
+    let similarity = SimilarityConfig::default();
+
+    let knn = build_weighted_graph(&days, &similarity, EdgeRule::Nearest(1));
+    assert_eq!(knn.adj_list[&0].len(), 1);
+    assert_eq!(knn.adj_list[&0][0].node, 1);
+    assert!((knn.adj_list[&0][0].distance - 10.0 / 6.0).abs() < 1e-9);
+    assert_eq!(knn.neighbors(2).count(), 1);
+
+    let threshold = build_weighted_graph(&days, &similarity, EdgeRule::Threshold(2.0));
+    assert_eq!(threshold.neighbors(0).collect::<Vec<_>>(), vec![1]);
+    assert!(!threshold.adj_list.contains_key(&2));
+}
+
+//This function makes a year-like set of days for the construction tests, using the seeded generator so runs repeat
+#[cfg(test)]
+fn synthetic_days(count: usize) -> Vec<DaySummary> {
+    use crate::common::{Lcg, PrecipitationIntensity, TemperatureCategory, Weather};
+    let mut random = Lcg(42);
+    let mut next = move |modulo: u64| random.below(modulo as usize) as u64;
+    (0..count)
+        .map(|i| {
+            let month = next(12) as u32 + 1;
+            let avg_temperature = next(400) as f64 / 10.0 - 10.0;
+            let total_rainfall = if next(4) == 0 { next(300) as f64 / 10.0 } else { 0.0 };
+            DaySummary {
+                month,
+                total_rentals: 5000 + next(30000) as i32,
+                weather: match next(4) {
+                    0 => Weather::Sunny,
+                    1 => Weather::Rainy,
+                    2 => Weather::Snowy,
+                    _ => Weather::Foggy,
+                },
+                temperature_category: match avg_temperature {
+                    t if t > 25.0 => TemperatureCategory::Hot,
+                    t if t < 10.0 => TemperatureCategory::Cold,
+                    _ => TemperatureCategory::Moderate,
+                },
+                precipitation: match next(3) {
+                    0 => PrecipitationIntensity::None,
+                    1 => PrecipitationIntensity::Light,
+                    _ => PrecipitationIntensity::Heavy,
+                },
+                avg_temperature,
+                total_rainfall,
+                day_type: if i % 7 >= 5 { crate::common::DayType::Weekend } else { crate::common::DayType::Weekday },
+                ..test_day(&i.to_string(), 0)
+            }
+        })
+        .collect()
+}
+
+#[test]
+//The test checks the bucketed, sorted construction against comparing every pair of days directly, whichever measured value leads the sort
+fn test_weighted_graph_matches_all_pairs() {
+    let days = synthetic_days(300);
+    let by_temperature = SimilarityConfig { temperature_c: 10.0, rainfall_mm: 5.0, ..SimilarityConfig::default() };
+    let by_rainfall = SimilarityConfig { temperature_c: 5.0, rainfall_mm: 10.0, ..SimilarityConfig::default() };
+    let unmeasured = SimilarityConfig::default();
+    let cases = [by_temperature, by_rainfall, unmeasured].into_iter().flat_map(|similarity| {
+        [EdgeRule::Nearest(4), EdgeRule::Threshold(12.0), EdgeRule::Nearest(0)].map(|rule| (similarity.clone(), rule))
+    });
+    for (similarity, rule) in cases {
+        let graph = build_weighted_graph(&days, &similarity, rule);
+        let mut expected: HashSet<(usize, usize)> = HashSet::new();
+        for i in 0..days.len() {
+            let mut distances: Vec<(usize, f64)> = (0..days.len())
+                .filter(|&j| j != i)
+                .map(|j| (j, similarity_score(&days[i], &conditions_of(&days[j]), &similarity)))
+                .collect();
+            distances.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+            match rule {
+                EdgeRule::Nearest(k) => distances.truncate(k),
+                EdgeRule::Threshold(epsilon) => distances.retain(|&(_, d)| d <= epsilon),
+            }
+            expected.extend(distances.into_iter().map(|(j, _)| (i.min(j), i.max(j))));
+        }
+        let built: HashSet<(usize, usize)> = graph
+            .adj_list
+            .iter()
+            .flat_map(|(&a, edges)| edges.iter().map(move |e| (a.min(e.node), a.max(e.node))))
+            .collect();
+        assert_eq!(built, expected);
+    }
+
+    let exact = build_graph(&days);
+    assert_eq!(exact.class_of.len(), days.len());
+    assert!(exact.neighbors(0).all(|j| j != 0 && class_key(&days[j]) == class_key(&days[0])));
+}
+
+#[test]
+//The test checks that dijkstra and A* agree on a shortest path that goes around a long direct edge
+fn test_shortest_paths() {
+    let make_day = |date: &str| DaySummary {
+        temperature_category: crate::common::TemperatureCategory::Cold,
+        avg_temperature: 0.0,
+        ..test_day(date, 1000)
+    };
+    let days = vec![make_day("01/01/2018"), make_day("02/01/2018"), make_day("03/01/2018"), make_day("04/01/2018")];
+    let mut adj_list: HashMap<usize, Vec<Edge>> = HashMap::new();
+    for (a, b, distance) in [(0, 1, 1.0), (1, 2, 1.0), (0, 2, 5.0)] {
+        adj_list.entry(a).or_default().push(Edge { node: b, distance });
+        adj_list.entry(b).or_default().push(Edge { node: a, distance });
+    }
+    let graph = WeightedGraph { adj_list };
+
+    let path = dijkstra_path(&graph, &days, 0, 2).unwrap();
+    let dates: Vec<&str> = path.steps.iter().map(|step| step.day.date.as_str()).collect();
+    assert_eq!(dates, vec!["01/01/2018", "02/01/2018", "03/01/2018"]);
+    assert_eq!(path.total_distance, 2.0);
+    assert_eq!(path.steps[1].cumulative, 1.0);
+
+    let guided = astar_path(&graph, &days, 0, 2, &SimilarityConfig::default()).unwrap();
+    assert_eq!(guided.total_distance, 2.0);
+
+    assert_eq!(degrees_of_separation(&graph, 0, 2), Some(1));
+    assert!(dijkstra_path(&graph, &days, 0, 3).is_none());
+    assert_eq!(degrees_of_separation(&graph, 0, 3), None);
+    assert_eq!(find_day(&days, "04/01/2018"), Some(3));
+
+    //Days one degree apart in a line: the heuristic now differs between days, so A* heads for the goal while Dijkstra spreads both ways
+    let similarity = SimilarityConfig { temperature_c: 10.0, temperature_c_range: 40.0, ..SimilarityConfig::default() };
+    let line: Vec<DaySummary> = (0..21)
+        .map(|i| DaySummary { avg_temperature: i as f64, ..make_day(&format!("{:02}/01/2018", i + 1)) })
+        .collect();
+    let graph = build_weighted_graph(&line, &similarity, EdgeRule::Nearest(2));
+    let plain = dijkstra_path(&graph, &line, 10, 20).unwrap();
+    let guided = astar_path(&graph, &line, 10, 20, &similarity).unwrap();
+    assert_eq!(plain.total_distance, 2.5);
+    assert_eq!(guided.total_distance, plain.total_distance);
+    assert_eq!(guided.steps.len(), 10);
+    assert!(guided.expanded < plain.expanded, "A* expanded {} days, Dijkstra {}", guided.expanded, plain.expanded);
 }
@@ -0,0 +1,407 @@
+//This module checks whether the differences in demand between groups of days are larger than chance would explain
+//Every group is compared with Welch's t-test and Mann-Whitney U, and all groups together with one-way ANOVA and Kruskal-Wallis
+//The p-values come from the t, F and chi-squared distributions, computed here with the incomplete beta and gamma functions
+use crate::analysis::ranks;
+use crate::common::Lcg;
+
+//Resamples drawn for every bootstrap interval, and the seed that keeps the intervals the same from run to run
+const BOOTSTRAP_RESAMPLES: usize = 2000;
+pub const BOOTSTRAP_SEED: u64 = 42;
+//Above this many groups only the tests of all groups together are printed, since the pairs would fill the screen
+const MAX_PAIRWISE_GROUPS: usize = 6;
+
+//This struct is the outcome of a test between two groups
+#[derive(Debug, Clone)]
+pub struct PairTest {
+    pub statistic: f64,
+    //degrees of freedom for the t-test, unused for Mann-Whitney
+    pub df: f64,
+    pub p_value: f64,
+    //Hedges' g for the t-test, rank-biserial correlation for Mann-Whitney
+    pub effect_size: f64,
+}
+
+//This struct is the outcome of a test over all groups together
+#[derive(Debug, Clone)]
+pub struct GroupTest {
+    pub statistic: f64,
+    pub df: (f64, f64),
+    pub p_value: f64,
+    //eta squared for ANOVA, epsilon squared for Kruskal-Wallis
+    pub effect_size: f64,
+}
+
+//This struct compares two of the groups
+#[derive(Debug, Clone)]
+pub struct PairComparison {
+    pub first: String,
+    pub second: String,
+    pub mean_difference: f64,
+    pub difference_interval: (f64, f64),
+    pub welch: PairTest,
+    pub mann_whitney: PairTest,
+}
+
+//This struct describes one group with a bootstrap interval for its mean
+#[derive(Debug, Clone)]
+pub struct GroupMean {
+    pub label: String,
+    pub count: usize,
+    pub mean: f64,
+    pub interval: (f64, f64),
+}
+
+//This struct is the whole significance report for one way of grouping the days
+#[derive(Debug, Clone)]
+pub struct SignificanceReport {
+    pub groups: Vec<GroupMean>,
+    pub anova: GroupTest,
+    pub kruskal_wallis: GroupTest,
+    pub pairs: Vec<PairComparison>,
+}
+
+//This function is the log of the gamma function, using the Lanczos approximation
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [76.18009172947146, -86.50532032941677, 24.01409824083091, -1.231739572450155, 0.1208650973866179e-2, -0.5395239384953e-5];
+    let tmp = x + 5.5 - (x + 0.5) * (x + 5.5).ln();
+    let series = COEFFICIENTS.iter().enumerate().fold(1.000000000190015, |sum, (i, c)| sum + c / (x + 1.0 + i as f64));
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+//This function is the continued fraction of the incomplete beta function (Lentz's method)
+fn beta_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let mut c = 1.0;
+    let mut d = 1.0 - (a + b) * x / (a + 1.0);
+    d = if d.abs() < TINY { TINY } else { d };
+    d = 1.0 / d;
+    let mut h = d;
+    for m in 1..300 {
+        let m = m as f64;
+        for numerator in [m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m)), -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0))] {
+            d = 1.0 + numerator * d;
+            d = if d.abs() < TINY { TINY } else { d };
+            c = 1.0 + numerator / c;
+            c = if c.abs() < TINY { TINY } else { c };
+            d = 1.0 / d;
+            h *= d * c;
+        }
+        if (d * c - 1.0).abs() < 1e-14 {
+            break;
+        }
+    }
+    h
+}
+
+//This function is the regularised incomplete beta function I_x(a, b)
+pub fn incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let front = (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    //the fraction converges quickly only on one side of the mean, so the other side uses the symmetry I_x(a, b) = 1 - I_1-x(b, a)
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_fraction(a, b, x) / a
+    } else {
+        1.0 - front * beta_fraction(b, a, 1.0 - x) / b
+    }
+}
+
+//This function is the regularised upper incomplete gamma function Q(a, x)
+pub fn upper_gamma(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let log_front = -x + a * x.ln() - ln_gamma(a);
+    if x < a + 1.0 {
+        //series for the lower function
+        let (mut term, mut sum, mut n) = (1.0 / a, 1.0 / a, a);
+        for _ in 0..500 {
+            n += 1.0;
+            term *= x / n;
+            sum += term;
+            if term.abs() < sum.abs() * 1e-15 {
+                break;
+            }
+        }
+        1.0 - sum * log_front.exp()
+    } else {
+        //continued fraction for the upper function
+        const TINY: f64 = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / TINY;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..500 {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            d = if d.abs() < TINY { TINY } else { d };
+            c = b + an / c;
+            c = if c.abs() < TINY { TINY } else { c };
+            d = 1.0 / d;
+            h *= d * c;
+            if (d * c - 1.0).abs() < 1e-15 {
+                break;
+            }
+        }
+        log_front.exp() * h
+    }
+}
+
+//Two-sided p-value of a t statistic
+fn t_p_value(t: f64, df: f64) -> f64 {
+    incomplete_beta(df / 2.0, 0.5, df / (df + t * t))
+}
+
+//Two-sided p-value of a standard normal statistic, which is the chi-squared tail of its square with one degree of freedom
+fn normal_p_value(z: f64) -> f64 {
+    upper_gamma(0.5, z * z / 2.0)
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+//Sample variance with n - 1 in the denominator
+fn variance(values: &[f64]) -> f64 {
+    let m = mean(values);
+    values.iter().map(|v| (v - m).powi(2)).sum::<f64>() / (values.len() - 1) as f64
+}
+
+//This function runs Welch's t-test, which does not assume the two groups vary equally; None when a group has fewer than 2 days
+pub fn welch_t_test(a: &[f64], b: &[f64]) -> Option<PairTest> {
+    if a.len() < 2 || b.len() < 2 {
+        return None;
+    }
+    let (na, nb) = (a.len() as f64, b.len() as f64);
+    let (va, vb) = (variance(a) / na, variance(b) / nb);
+    if va + vb == 0.0 {
+        return None;
+    }
+    let t = (mean(a) - mean(b)) / (va + vb).sqrt();
+    let df = (va + vb).powi(2) / (va * va / (na - 1.0) + vb * vb / (nb - 1.0));
+    //Hedges' g: Cohen's d on the pooled standard deviation, corrected for small groups
+    let pooled = (((na - 1.0) * variance(a) + (nb - 1.0) * variance(b)) / (na + nb - 2.0)).sqrt();
+    let correction = 1.0 - 3.0 / (4.0 * (na + nb) - 9.0);
+    let effect_size = if pooled > 0.0 { (mean(a) - mean(b)) / pooled * correction } else { 0.0 };
+    Some(PairTest { statistic: t, df, p_value: t_p_value(t, df), effect_size })
+}
+
+//This function runs the Mann-Whitney U test on the ranks, with the normal approximation corrected for ties
+pub fn mann_whitney(a: &[f64], b: &[f64]) -> Option<PairTest> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+    let (na, nb) = (a.len() as f64, b.len() as f64);
+    let combined: Vec<f64> = a.iter().chain(b).copied().collect();
+    let ranked = ranks(&combined);
+    let rank_sum: f64 = ranked[..a.len()].iter().sum();
+    let u = rank_sum - na * (na + 1.0) / 2.0;
+
+    let n = na + nb;
+    let tie_term: f64 = tie_sizes(&combined).iter().map(|&t| t * t * t - t).sum();
+    let sigma = (na * nb / 12.0 * ((n + 1.0) - tie_term / (n * (n - 1.0)))).sqrt();
+    if sigma == 0.0 {
+        return None;
+    }
+    let z = (u - na * nb / 2.0) / sigma;
+    Some(PairTest { statistic: u, df: 0.0, p_value: normal_p_value(z), effect_size: 2.0 * u / (na * nb) - 1.0 })
+}
+
+//This function gives the size of every run of equal values
+fn tie_sizes(values: &[f64]) -> Vec<f64> {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    sorted.chunk_by(|a, b| a == b).map(|run| run.len() as f64).filter(|&t| t > 1.0).collect()
+}
+
+//This function runs one-way ANOVA over the groups, leaving out empty groups
+pub fn anova(groups: &[&[f64]]) -> Option<GroupTest> {
+    let groups: Vec<&[f64]> = groups.iter().copied().filter(|g| !g.is_empty()).collect();
+    let k = groups.len() as f64;
+    let n: f64 = groups.iter().map(|g| g.len() as f64).sum();
+    if k < 2.0 || n <= k {
+        return None;
+    }
+    let grand = groups.iter().flat_map(|g| g.iter()).sum::<f64>() / n;
+    let between: f64 = groups.iter().map(|g| g.len() as f64 * (mean(g) - grand).powi(2)).sum();
+    let within: f64 = groups.iter().map(|g| g.iter().map(|v| (v - mean(g)).powi(2)).sum::<f64>()).sum();
+    if within == 0.0 {
+        return None;
+    }
+    let (df1, df2) = (k - 1.0, n - k);
+    let f = (between / df1) / (within / df2);
+    Some(GroupTest {
+        statistic: f,
+        df: (df1, df2),
+        p_value: incomplete_beta(df2 / 2.0, df1 / 2.0, df2 / (df2 + df1 * f)),
+        effect_size: between / (between + within),
+    })
+}
+
+//This function runs the Kruskal-Wallis test, the rank version of ANOVA, with the tie correction
+pub fn kruskal_wallis(groups: &[&[f64]]) -> Option<GroupTest> {
+    let groups: Vec<&[f64]> = groups.iter().copied().filter(|g| !g.is_empty()).collect();
+    let k = groups.len() as f64;
+    let combined: Vec<f64> = groups.iter().flat_map(|g| g.iter()).copied().collect();
+    let n = combined.len() as f64;
+    if k < 2.0 || n <= k {
+        return None;
+    }
+    let ranked = ranks(&combined);
+    let mut start = 0;
+    let mut h = 0.0;
+    for group in &groups {
+        let rank_sum: f64 = ranked[start..start + group.len()].iter().sum();
+        h += rank_sum * rank_sum / group.len() as f64;
+        start += group.len();
+    }
+    h = 12.0 / (n * (n + 1.0)) * h - 3.0 * (n + 1.0);
+    let correction = 1.0 - tie_sizes(&combined).iter().map(|&t| t * t * t - t).sum::<f64>() / (n * n * n - n);
+    if correction == 0.0 {
+        return None;
+    }
+    h /= correction;
+    Some(GroupTest {
+        statistic: h,
+        df: (k - 1.0, 0.0),
+        p_value: upper_gamma((k - 1.0) / 2.0, h / 2.0),
+        effect_size: h / (n - 1.0),
+    })
+}
+
+//This function draws a group of the same size with replacement and gives its mean
+fn mean_of_resample(random: &mut Lcg, values: &[f64]) -> f64 {
+    (0..values.len()).map(|_| values[random.below(values.len())]).sum::<f64>() / values.len() as f64
+}
+
+//This function gives the 95% percentile bootstrap interval of a statistic computed on resampled groups
+fn bootstrap(mut statistic: impl FnMut(&mut Lcg) -> f64) -> (f64, f64) {
+    let mut resampler = Lcg(BOOTSTRAP_SEED);
+    let mut values: Vec<f64> = (0..BOOTSTRAP_RESAMPLES).map(|_| statistic(&mut resampler)).collect();
+    values.sort_by(|a, b| a.total_cmp(b));
+    (crate::stats::percentile(&values, 0.025), crate::stats::percentile(&values, 0.975))
+}
+
+//This function gives the bootstrap interval of the mean of one group
+pub fn bootstrap_mean(values: &[f64]) -> (f64, f64) {
+    bootstrap(|r| mean_of_resample(r, values))
+}
+
+//This function gives the bootstrap interval of the difference between the means of two groups
+pub fn bootstrap_difference(a: &[f64], b: &[f64]) -> (f64, f64) {
+    bootstrap(|r| mean_of_resample(r, a) - mean_of_resample(r, b))
+}
+
+//This function runs every test on the labelled groups; None when fewer than two groups can be compared
+pub fn significance_report(groups: &[(Vec<String>, Vec<f64>)]) -> Option<SignificanceReport> {
+    let values: Vec<&[f64]> = groups.iter().map(|(_, v)| v.as_slice()).collect();
+    let anova = anova(&values)?;
+    let kruskal_wallis = kruskal_wallis(&values)?;
+    let label = |key: &[String]| key.join(" / ");
+
+    let means = groups
+        .iter()
+        .filter(|(_, v)| !v.is_empty())
+        .map(|(key, v)| GroupMean { label: label(key), count: v.len(), mean: mean(v), interval: bootstrap_mean(v) })
+        .collect();
+
+    let mut pairs = Vec::new();
+    if groups.len() <= MAX_PAIRWISE_GROUPS {
+        for (i, (first, a)) in groups.iter().enumerate() {
+            for (second, b) in &groups[i + 1..] {
+                if let (Some(welch), Some(mann_whitney)) = (welch_t_test(a, b), mann_whitney(a, b)) {
+                    pairs.push(PairComparison {
+                        first: label(first),
+                        second: label(second),
+                        mean_difference: mean(a) - mean(b),
+                        difference_interval: bootstrap_difference(a, b),
+                        welch,
+                        mann_whitney,
+                    });
+                }
+            }
+        }
+    }
+    Some(SignificanceReport { groups: means, anova, kruskal_wallis, pairs })
+}
+
+//This function formats a p-value, switching to a bound once it is too small to matter
+fn show_p(p: f64) -> String {
+    if p < 0.0001 { "<0.0001".to_string() } else { format!("{:.4}", p) }
+}
+
+//This function prints the group means, the tests over all groups and then every pair
+pub fn print_significance(report: &SignificanceReport) {
+    println!("  Group means with 95% bootstrap intervals:");
+    for group in &report.groups {
+        println!("    {:<30} {:>5} days {:>8.0} ({:.0} - {:.0})", group.label, group.count, group.mean, group.interval.0, group.interval.1);
+    }
+    let a = &report.anova;
+    println!("  ANOVA: F({:.0}, {:.0}) = {:.2}, p = {}, eta² = {:.3}", a.df.0, a.df.1, a.statistic, show_p(a.p_value), a.effect_size);
+    let k = &report.kruskal_wallis;
+    println!("  Kruskal-Wallis: H({:.0}) = {:.2}, p = {}, epsilon² = {:.3}", k.df.0, k.statistic, show_p(k.p_value), k.effect_size);
+    if report.pairs.is_empty() {
+        return;
+    }
+    println!(
+        "  {:<30} {:>9} {:>19} {:>8} {:>6} {:>9} {:>8} {:>9} {:>8}",
+        "Pair", "Diff", "95% bootstrap CI", "Welch t", "df", "p", "g", "MW p", "r"
+    );
+    for pair in &report.pairs {
+        println!(
+            "  {:<30} {:>9.0} {:>19} {:>8.2} {:>6.1} {:>9} {:>8.2} {:>9} {:>8.2}",
+            format!("{} vs {}", pair.first, pair.second),
+            pair.mean_difference,
+            format!("{:.0} - {:.0}", pair.difference_interval.0, pair.difference_interval.1),
+            pair.welch.statistic,
+            pair.welch.df,
+            show_p(pair.welch.p_value),
+            pair.welch.effect_size,
+            show_p(pair.mann_whitney.p_value),
+            pair.mann_whitney.effect_size
+        );
+    }
+}
+
+//The tests check the distributions against known values and the tests on groups with a clear and with no difference
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distributions() {
+        //t = 2.228 with 10 degrees of freedom is the two-sided 5% point, and z = 1.96 for the normal distribution
+        assert!((t_p_value(2.228, 10.0) - 0.05).abs() < 1e-3);
+        assert!((normal_p_value(1.96) - 0.05).abs() < 1e-3);
+        //chi-squared with 2 degrees of freedom has the tail exp(-x / 2)
+        assert!((upper_gamma(1.0, 3.0) - (-3.0_f64).exp()).abs() < 1e-10);
+        assert!((incomplete_beta(2.0, 3.0, 0.4) - 0.5248).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_group_tests() {
+        let low = [10.0, 12.0, 11.0, 13.0, 9.0, 12.0, 10.0, 11.0];
+        let high = [20.0, 22.0, 19.0, 21.0, 23.0, 20.0, 22.0, 21.0];
+        let welch = welch_t_test(&low, &high).unwrap();
+        assert!(welch.p_value < 0.0001 && welch.effect_size < -2.0);
+        let mw = mann_whitney(&low, &high).unwrap();
+        assert_eq!(mw.statistic, 0.0);
+        assert_eq!(mw.effect_size, -1.0);
+        assert!(mw.p_value < 0.01);
+
+        let (ci_low, ci_high) = bootstrap_difference(&low, &high);
+        assert!(ci_low < -10.5 && ci_high > -9.5 && ci_high < 0.0);
+
+        let same = [11.0, 10.0, 12.0, 13.0, 9.0, 11.0, 12.0, 10.0];
+        let report = anova(&[&low, &same]).unwrap();
+        assert!(report.p_value > 0.5);
+        let report = kruskal_wallis(&[&low, &same, &high]).unwrap();
+        assert_eq!(report.df.0, 2.0);
+        assert!(report.p_value < 0.001);
+    }
+}
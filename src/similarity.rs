@@ -0,0 +1,229 @@
+//This module defines how far apart a day and a query are, with weights that can be loaded from a file or overridden on the command line
+use crate::common::{Conditions, DaySummary, PrecipitationIntensity, TemperatureCategory};
+use std::error::Error;
+use std::fs;
+
+//This struct holds the weight of each feature in the similarity score
+//Every distance is scaled to 0..1 before it is weighted, so a weight is the penalty for the largest possible difference
+//The numeric temperature and rainfall distances are only used when the query has a value for them and their weight is above 0
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimilarityConfig {
+    pub month: f64,
+    pub weather: f64,
+    pub temperature: f64,
+    pub precipitation: f64,
+    pub temperature_c: f64,
+    pub rainfall_mm: f64,
+    //differences of at least this many degrees / millimetres count as the full distance
+    pub temperature_c_range: f64,
+    pub rainfall_mm_range: f64,
+}
+
+impl Default for SimilarityConfig {
+    fn default() -> Self {
+        SimilarityConfig {
+            month: 10.0,
+            weather: 30.0,
+            temperature: 20.0,
+            precipitation: 15.0,
+            temperature_c: 0.0,
+            rainfall_mm: 0.0,
+            temperature_c_range: 20.0,
+            rainfall_mm_range: 30.0,
+        }
+    }
+}
+
+impl SimilarityConfig {
+    //This function lists every weight with the name used in config files
+    pub fn entries(&self) -> [(&'static str, f64); 8] {
+        [
+            ("month", self.month),
+            ("weather", self.weather),
+            ("temperature", self.temperature),
+            ("precipitation", self.precipitation),
+            ("temperature_c", self.temperature_c),
+            ("rainfall_mm", self.rainfall_mm),
+            ("temperature_c_range", self.temperature_c_range),
+            ("rainfall_mm_range", self.rainfall_mm_range),
+        ]
+    }
+
+    //This function sets one weight by its name, which is shared by the config file and the command line
+    pub fn set(&mut self, key: &str, value: f64) -> Result<(), String> {
+        if !value.is_finite() || value < 0.0 {
+            return Err(format!("{} must be a non-negative number", key));
+        }
+        match key {
+            "month" => self.month = value,
+            "weather" => self.weather = value,
+            "temperature" => self.temperature = value,
+            "precipitation" => self.precipitation = value,
+            "temperature_c" => self.temperature_c = value,
+            "rainfall_mm" => self.rainfall_mm = value,
+            "temperature_c_range" if value > 0.0 => self.temperature_c_range = value,
+            "rainfall_mm_range" if value > 0.0 => self.rainfall_mm_range = value,
+            "temperature_c_range" | "rainfall_mm_range" => return Err(format!("{} must be above 0", key)),
+            _ => return Err(format!("Unknown similarity weight: {}", key)),
+        }
+        Ok(())
+    }
+}
+
+//This function loads a config file made of "name = value" lines, with # starting a comment
+//Weights missing from the file keep their default value
+pub fn load_config(path: &str) -> Result<SimilarityConfig, Box<dyn Error>> {
+    let mut config = SimilarityConfig::default();
+    for (number, line) in fs::read_to_string(path)?.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {}: expected name = value", number + 1))?;
+        let value: f64 = value
+            .trim()
+            .parse()
+            .map_err(|_| format!("line {}: {} is not a number", number + 1, value.trim()))?;
+        config.set(key.trim(), value).map_err(|e| format!("line {}: {}", number + 1, e))?;
+    }
+    Ok(config)
+}
+
+//This function writes the config in the same "name = value" format that load_config reads
+pub fn save_config(path: &str, config: &SimilarityConfig, header: &str) -> Result<(), Box<dyn Error>> {
+    let mut text = String::new();
+    for line in header.lines() {
+        text.push_str(&format!("# {}\n", line));
+    }
+    for (key, value) in config.entries() {
+        text.push_str(&format!("{} = {}\n", key, value));
+    }
+    fs::write(path, text)?;
+    Ok(())
+}
+
+//Months are compared around the year so December and January are one month apart, and six months is the furthest possible
+fn month_distance(a: u32, b: u32) -> f64 {
+    if !(1..=12).contains(&a) || !(1..=12).contains(&b) {
+        return 1.0;
+    }
+    let diff = a.abs_diff(b);
+    diff.min(12 - diff) as f64 / 6.0
+}
+
+//Temperature categories are ordered cold < moderate < hot, so cold and hot are the furthest apart
+fn temperature_distance(a: &TemperatureCategory, b: &TemperatureCategory) -> f64 {
+    let rank = |t: &TemperatureCategory| match t {
+        TemperatureCategory::Cold => 0.0_f64,
+        TemperatureCategory::Moderate => 1.0,
+        TemperatureCategory::Hot => 2.0,
+    };
+    (rank(a) - rank(b)).abs() / 2.0
+}
+
+//Precipitation is ordered none < light < heavy in the same way
+fn precipitation_distance(a: &PrecipitationIntensity, b: &PrecipitationIntensity) -> f64 {
+    let rank = |p: &PrecipitationIntensity| match p {
+        PrecipitationIntensity::None => 0.0_f64,
+        PrecipitationIntensity::Light => 1.0,
+        PrecipitationIntensity::Heavy => 2.0,
+    };
+    (rank(a) - rank(b)).abs() / 2.0
+}
+
+//This function is the part of the score that comes from the month and the weather categories
+pub fn category_score(day: &DaySummary, query: &Conditions, config: &SimilarityConfig) -> f64 {
+    let mut score = config.month * month_distance(day.month, query.month)
+        + config.temperature * temperature_distance(&day.temperature_category, &query.temperature)
+        + config.precipitation * precipitation_distance(&day.precipitation, &query.precipitation);
+    if day.weather != query.weather {
+        score += config.weather;
+    }
+    score
+}
+
+//This function is the part of the score that comes from the measured temperature and rainfall, which is never negative
+pub fn measured_score(day: &DaySummary, query: &Conditions, config: &SimilarityConfig) -> f64 {
+    let mut score = 0.0;
+    if let Some(temperature_c) = query.temperature_c {
+        let distance = (day.avg_temperature - temperature_c).abs() / config.temperature_c_range;
+        score += config.temperature_c * distance.min(1.0);
+    }
+    if let Some(rainfall_mm) = query.rainfall_mm {
+        let distance = (day.total_rainfall - rainfall_mm).abs() / config.rainfall_mm_range;
+        score += config.rainfall_mm * distance.min(1.0);
+    }
+    score
+}
+
+//This function calculates the similarity score. The higher the score is, the more the day is dissimilar from the query
+pub fn similarity_score(day: &DaySummary, query: &Conditions, config: &SimilarityConfig) -> f64 {
+    category_score(day, query, config) + measured_score(day, query, config)
+}
+
+//This function turns a historical day into the query that describes it, including its measured temperature and rainfall
+//Scoring a day against the conditions of another day gives the distance between them, which is the same in both directions
+pub fn conditions_of(day: &DaySummary) -> Conditions {
+    Conditions {
+        month: day.month,
+        weather: day.weather.clone(),
+        temperature: day.temperature_category.clone(),
+        precipitation: day.precipitation.clone(),
+        temperature_c: Some(day.avg_temperature),
+        rainfall_mm: Some(day.total_rainfall),
+    }
+}
+
+//The tests check the graded distances and that a config file overrides only the weights it lists
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{test_day, Weather};
+
+    #[test]
+    fn test_graded_distances() {
+        assert_eq!(month_distance(12, 1), 1.0 / 6.0);
+        assert_eq!(month_distance(1, 7), 1.0);
+        assert_eq!(month_distance(0, 7), 1.0);
+        assert_eq!(temperature_distance(&TemperatureCategory::Cold, &TemperatureCategory::Hot), 1.0);
+        assert_eq!(precipitation_distance(&PrecipitationIntensity::Light, &PrecipitationIntensity::Heavy), 0.5);
+
+        let day = DaySummary {
+            weather: Weather::Snowy,
+            temperature_category: TemperatureCategory::Cold,
+            precipitation: PrecipitationIntensity::Light,
+            avg_temperature: -2.0,
+            ..test_day("01/12/2017", 5000)
+        };
+        let query = Conditions {
+            month: 1,
+            weather: Weather::Snowy,
+            temperature: TemperatureCategory::Cold,
+            precipitation: PrecipitationIntensity::Heavy,
+            temperature_c: Some(8.0),
+            rainfall_mm: None,
+        };
+        let config = SimilarityConfig { temperature_c: 4.0, ..SimilarityConfig::default() };
+        let expected = 10.0 / 6.0 + 15.0 * 0.5 + 4.0 * 0.5;
+        assert!((similarity_score(&day, &query, &config) - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_load_config() {
+        let path = "test_similarity.cfg";
+        fs::write(path, "# learned weights\nmonth = 4\nrainfall_mm=2.5\n").unwrap();
+        let config = load_config(path).unwrap();
+        assert_eq!(config.month, 4.0);
+        assert_eq!(config.rainfall_mm, 2.5);
+        assert_eq!(config.weather, 30.0);
+
+        save_config(path, &config, "saved").unwrap();
+        assert_eq!(load_config(path).unwrap(), config);
+
+        fs::write(path, "humidity = 1\n").unwrap();
+        assert!(load_config(path).is_err());
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,349 @@
+//This module summarises the daily demand, overall and grouped by any combination of season, weather, temperature, precipitation and day type
+//It also splits the demand by measured wind speed, hour by hour and by the windiest hour of each day
+use crate::common::{DaySummary, RawRecord, Season};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+//This enum is one property the days can be grouped by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dimension {
+    Season,
+    Weather,
+    Temperature,
+    Precipitation,
+    DayType,
+}
+
+pub const ALL_DIMENSIONS: [Dimension; 5] = [Dimension::Season, Dimension::Weather, Dimension::Temperature, Dimension::Precipitation, Dimension::DayType];
+
+impl Dimension {
+    pub fn name(self) -> &'static str {
+        match self {
+            Dimension::Season => "Season",
+            Dimension::Weather => "Weather",
+            Dimension::Temperature => "Temperature",
+            Dimension::Precipitation => "Precipitation",
+            Dimension::DayType => "Day type",
+        }
+    }
+
+    //This function gives the group of the day for this property, with a rank so the groups print in their natural order
+    fn group_of(self, day: &DaySummary) -> (u8, String) {
+        match self {
+            Dimension::Season => {
+                let season = Season::of_month(day.month);
+                (season as u8, season.to_string())
+            }
+            Dimension::Weather => (day.weather.clone() as u8, day.weather.to_string()),
+            Dimension::Temperature => (day.temperature_category.clone() as u8, day.temperature_category.to_string()),
+            Dimension::Precipitation => (day.precipitation.clone() as u8, day.precipitation.to_string()),
+            Dimension::DayType => (day.day_type as u8, day.day_type.to_string()),
+        }
+    }
+}
+
+impl FromStr for Dimension {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "season" => Ok(Dimension::Season),
+            "weather" => Ok(Dimension::Weather),
+            "temperature" | "temp" => Ok(Dimension::Temperature),
+            "precipitation" | "precip" => Ok(Dimension::Precipitation),
+            "daytype" | "day-type" | "day_type" => Ok(Dimension::DayType),
+            _ => Err(format!("unknown group: {} (season, weather, temperature, precipitation or day-type)", s)),
+        }
+    }
+}
+
+//This struct describes the daily rentals of one group of days
+#[derive(Debug, Clone, PartialEq)]
+pub struct DemandStats {
+    pub count: usize,
+    pub mean: f64,
+    pub median: f64,
+    //sample standard deviation, 0 for a single day
+    pub std_dev: f64,
+    pub min: f64,
+    pub max: f64,
+    pub p10: f64,
+    pub p25: f64,
+    pub p75: f64,
+    pub p90: f64,
+}
+
+//This struct is one group of the report, the key holding one label per grouped property
+#[derive(Debug, Clone)]
+pub struct GroupStats {
+    pub key: Vec<String>,
+    pub stats: DemandStats,
+}
+
+//This struct is the full report: the properties grouped by, every day together and each group
+#[derive(Debug, Clone)]
+pub struct DemandReport {
+    pub dimensions: Vec<Dimension>,
+    pub overall: DemandStats,
+    pub groups: Vec<GroupStats>,
+}
+
+//This function interpolates linearly between the two closest ranks of the sorted values, p going from 0 to 1
+pub fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let position = p.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let below = position.floor() as usize;
+    let above = position.ceil() as usize;
+    sorted[below] + (sorted[above] - sorted[below]) * (position - below as f64)
+}
+
+//This function computes the statistics of the values, or None when there are none
+pub fn demand_stats(values: &[f64]) -> Option<DemandStats> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let count = sorted.len();
+    let mean = sorted.iter().sum::<f64>() / count as f64;
+    let variance = if count > 1 {
+        sorted.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (count - 1) as f64
+    } else {
+        0.0
+    };
+    Some(DemandStats {
+        count,
+        mean,
+        median: percentile(&sorted, 0.5),
+        std_dev: variance.sqrt(),
+        min: sorted[0],
+        max: sorted[count - 1],
+        p10: percentile(&sorted, 0.1),
+        p25: percentile(&sorted, 0.25),
+        p75: percentile(&sorted, 0.75),
+        p90: percentile(&sorted, 0.9),
+    })
+}
+
+//This function splits the daily rentals by every combination of the given properties that occurs, in the natural order of the groups
+pub fn group_values(days: &[DaySummary], dimensions: &[Dimension]) -> Vec<(Vec<String>, Vec<f64>)> {
+    let mut grouped: BTreeMap<Vec<(u8, String)>, Vec<f64>> = BTreeMap::new();
+    for day in days {
+        let key = dimensions.iter().map(|dimension| dimension.group_of(day)).collect();
+        grouped.entry(key).or_default().push(day.total_rentals as f64);
+    }
+    grouped
+        .into_iter()
+        .map(|(key, values)| (key.into_iter().map(|(_, label)| label).collect(), values))
+        .collect()
+}
+
+//This function describes the rentals of each group of days
+//With no properties there is a single group, and with no days there is no report
+pub fn demand_report(days: &[DaySummary], dimensions: &[Dimension]) -> Option<DemandReport> {
+    let all: Vec<f64> = days.iter().map(|day| day.total_rentals as f64).collect();
+    let overall = demand_stats(&all)?;
+    let groups = group_values(days, dimensions)
+        .into_iter()
+        .filter_map(|(key, values)| Some(GroupStats { key, stats: demand_stats(&values)? }))
+        .collect();
+    Some(DemandReport { dimensions: dimensions.to_vec(), overall, groups })
+}
+
+//This struct splits the demand by wind speed band, for single hours and for whole days by their windiest hour
+#[derive(Debug, Clone)]
+pub struct WindReport {
+    pub hourly: Vec<GroupStats>,
+    pub daily: Vec<GroupStats>,
+    pub hourly_overall: DemandStats,
+    pub daily_overall: DemandStats,
+    //Pearson correlation between wind speed and rentals, None when either does not vary
+    pub hourly_correlation: Option<f64>,
+    pub daily_correlation: Option<f64>,
+}
+
+//Upper limits in m/s of the wind bands, following the Beaufort scale; anything faster falls in the last band
+const WIND_BANDS: [(f64, &str); 3] = [(1.6, "Calm to light air (<1.6 m/s)"), (3.4, "Light breeze (1.6-3.3 m/s)"), (5.5, "Gentle breeze (3.4-5.4 m/s)")];
+const STRONGEST_BAND: &str = "Moderate breeze or more (5.5+ m/s)";
+
+//This function gives the position and name of the band a wind speed falls in
+fn wind_band(speed: f64) -> (usize, &'static str) {
+    WIND_BANDS
+        .iter()
+        .enumerate()
+        .find(|(_, (limit, _))| speed < *limit)
+        .map_or((WIND_BANDS.len(), STRONGEST_BAND), |(i, (_, name))| (i, *name))
+}
+
+//This function computes the Pearson correlation of two equally long series
+pub fn pearson(xs: &[f64], ys: &[f64]) -> Option<f64> {
+    let n = xs.len().min(ys.len());
+    if n < 2 {
+        return None;
+    }
+    let mean_x = xs[..n].iter().sum::<f64>() / n as f64;
+    let mean_y = ys[..n].iter().sum::<f64>() / n as f64;
+    let (mut covariance, mut var_x, mut var_y) = (0.0, 0.0, 0.0);
+    for (x, y) in xs.iter().zip(ys) {
+        covariance += (x - mean_x) * (y - mean_y);
+        var_x += (x - mean_x).powi(2);
+        var_y += (y - mean_y).powi(2);
+    }
+    if var_x == 0.0 || var_y == 0.0 {
+        return None;
+    }
+    Some(covariance / (var_x * var_y).sqrt())
+}
+
+//This function groups (wind speed, rentals) pairs into the wind bands
+fn band_groups(pairs: &[(f64, f64)]) -> Vec<GroupStats> {
+    let mut grouped: BTreeMap<(usize, &str), Vec<f64>> = BTreeMap::new();
+    for &(speed, rentals) in pairs {
+        grouped.entry(wind_band(speed)).or_default().push(rentals);
+    }
+    grouped
+        .into_iter()
+        .filter_map(|((_, name), values)| Some(GroupStats { key: vec![name.to_string()], stats: demand_stats(&values)? }))
+        .collect()
+}
+
+//This function builds the wind report from the hourly records, or None when there are none
+pub fn wind_report(records: &[RawRecord]) -> Option<WindReport> {
+    let hourly: Vec<(f64, f64)> = records.iter().map(|r| (r.wind_speed, r.rented_bike_count as f64)).collect();
+
+    //Each day is described by its windiest hour and its total rentals
+    let mut days: BTreeMap<&str, (f64, f64)> = BTreeMap::new();
+    for record in records {
+        let day = days.entry(record.date.as_str()).or_insert((f64::MIN, 0.0));
+        day.0 = day.0.max(record.wind_speed);
+        day.1 += record.rented_bike_count as f64;
+    }
+    let daily: Vec<(f64, f64)> = days.into_values().collect();
+
+    let speeds = |pairs: &[(f64, f64)]| pairs.iter().map(|p| p.0).collect::<Vec<f64>>();
+    let rentals = |pairs: &[(f64, f64)]| pairs.iter().map(|p| p.1).collect::<Vec<f64>>();
+    Some(WindReport {
+        hourly: band_groups(&hourly),
+        daily: band_groups(&daily),
+        hourly_overall: demand_stats(&rentals(&hourly))?,
+        daily_overall: demand_stats(&rentals(&daily))?,
+        hourly_correlation: pearson(&speeds(&hourly), &rentals(&hourly)),
+        daily_correlation: pearson(&speeds(&daily), &rentals(&daily)),
+    })
+}
+
+fn print_row(label: &str, stats: &DemandStats) {
+    println!(
+        "  {:<36} {:>5} {:>8.0} {:>8.0} {:>8.0} {:>8.0} {:>8.0} {:>8.0} {:>8.0} {:>8.0} {:>8.0}",
+        label, stats.count, stats.mean, stats.median, stats.std_dev, stats.min, stats.p10, stats.p25, stats.p75, stats.p90, stats.max
+    );
+}
+
+//This function prints one table with a row per group and the overall row last
+fn print_table(title: &str, groups: &[GroupStats], overall: &DemandStats) {
+    println!("[{}]", title);
+    println!(
+        "  {:<36} {:>5} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8}",
+        "Group", "Count", "Mean", "Median", "Std dev", "Min", "P10", "P25", "P75", "P90", "Max"
+    );
+    for group in groups {
+        print_row(&group.key.join(" / "), &group.stats);
+    }
+    print_row("All", overall);
+}
+
+//This function prints the report as a table with one row per group
+pub fn print_report(report: &DemandReport) {
+    let names: Vec<&str> = report.dimensions.iter().map(|dimension| dimension.name()).collect();
+    let title = format!("Daily bike demand by {}", if names.is_empty() { "day".to_string() } else { names.join(", ") });
+    print_table(&title, &report.groups, &report.overall);
+}
+
+//This function prints both wind tables followed by the correlation of wind speed with rentals
+pub fn print_wind_report(report: &WindReport) {
+    let correlation = |r: Option<f64>| r.map_or("n/a".to_string(), |r| format!("{:.3}", r));
+    print_table("Hourly bike demand by wind speed", &report.hourly, &report.hourly_overall);
+    println!("  Correlation of wind speed with hourly rentals: {}", correlation(report.hourly_correlation));
+    println!();
+    print_table("Daily bike demand by the windiest hour of the day", &report.daily, &report.daily_overall);
+    println!("  Correlation of maximum wind speed with daily rentals: {}", correlation(report.daily_correlation));
+}
+
+//The tests check the statistics on known values and that combined groups split the days correctly
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{test_day, DayType, Weather};
+
+    #[test]
+    fn test_demand_stats() {
+        let stats = demand_stats(&[4.0, 1.0, 3.0, 2.0, 5.0]).unwrap();
+        assert_eq!(stats.count, 5);
+        assert_eq!(stats.mean, 3.0);
+        assert_eq!(stats.median, 3.0);
+        assert!((stats.std_dev - 2.5_f64.sqrt()).abs() < 1e-9);
+        assert_eq!((stats.min, stats.max), (1.0, 5.0));
+        assert_eq!(stats.p25, 2.0);
+        assert!((stats.p90 - 4.6).abs() < 1e-9);
+        assert!(demand_stats(&[]).is_none());
+    }
+
+    #[test]
+    fn test_demand_report_groups() {
+        let make_day = |month: u32, total_rentals: i32, weather: Weather, day_type: DayType| DaySummary {
+            month,
+            weather,
+            avg_temperature: 15.0,
+            day_type,
+            ..test_day("", total_rentals)
+        };
+        let days = vec![
+            make_day(7, 100, Weather::Sunny, DayType::Weekday),
+            make_day(7, 300, Weather::Sunny, DayType::Weekday),
+            make_day(1, 50, Weather::Snowy, DayType::Weekend),
+            make_day(4, 200, Weather::Sunny, DayType::Holiday),
+        ];
+        let report = demand_report(&days, &[Dimension::Season, Dimension::Weather]).unwrap();
+        let keys: Vec<String> = report.groups.iter().map(|g| g.key.join("/")).collect();
+        assert_eq!(keys, ["Spring/Sunny", "Summer/Sunny", "Winter/Snowy"]);
+        assert_eq!(report.groups[1].stats.mean, 200.0);
+        assert_eq!(report.overall.count, 4);
+
+        let report = demand_report(&days, &[Dimension::DayType]).unwrap();
+        assert_eq!(report.groups.len(), 3);
+        assert_eq!("day-type".parse::<Dimension>(), Ok(Dimension::DayType));
+    }
+
+    #[test]
+    fn test_wind_report() {
+        let record = |date: &str, wind_speed: f64, rented_bike_count: i32| RawRecord {
+            date: date.to_string(),
+            rented_bike_count,
+            hour: 0,
+            temperature: 10.0,
+            humidity: 50.0,
+            wind_speed,
+            visibility: 2000.0,
+            dew_point_temp: 0.0,
+            solar_radiation: 0.0,
+            rainfall: 0.0,
+            snowfall: 0.0,
+            seasons: "Spring".to_string(),
+            holiday: "No Holiday".to_string(),
+            functioning_day: "Yes".to_string(),
+            month: 4,
+        };
+        let records = vec![record("a", 0.5, 300), record("a", 2.0, 200), record("b", 4.0, 100), record("b", 6.0, 50)];
+        let report = wind_report(&records).unwrap();
+        assert_eq!(report.hourly.len(), 4);
+        assert_eq!(report.hourly[0].key, ["Calm to light air (<1.6 m/s)"]);
+        //day a peaks at 2.0 m/s and day b at 6.0 m/s
+        let daily: Vec<(String, f64)> = report.daily.iter().map(|g| (g.key[0].clone(), g.stats.mean)).collect();
+        assert_eq!(daily, [("Light breeze (1.6-3.3 m/s)".to_string(), 500.0), (STRONGEST_BAND.to_string(), 150.0)]);
+        assert!(report.hourly_correlation.unwrap() < -0.9);
+        assert_eq!(report.daily_correlation, Some(-1.0));
+        assert_eq!(pearson(&[1.0, 1.0], &[2.0, 3.0]), None);
+    }
+}
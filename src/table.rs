@@ -0,0 +1,36 @@
+//This module reads and writes the tables of rows shared by the batch, forecast, hourly and analysis files, as CSV or as JSON
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::error::Error;
+use std::fs::{self, File};
+
+//Tables are read and written as JSON when the file name ends in .json, and as CSV otherwise
+pub fn is_json(path: &str) -> bool {
+    path.to_lowercase().ends_with(".json")
+}
+
+//This function reads every row of a CSV file, or of a JSON array when the path ends in .json
+//Each row is parsed on its own, so the caller decides whether a bad row is skipped or fails the whole file
+pub fn read_rows<T: DeserializeOwned>(path: &str) -> Result<Vec<Result<T, String>>, Box<dyn Error>> {
+    if is_json(path) {
+        let values: Vec<serde_json::Value> = serde_json::from_str(&fs::read_to_string(path)?)?;
+        Ok(values.into_iter().map(|v| serde_json::from_value(v).map_err(|e| e.to_string())).collect())
+    } else {
+        let mut rdr = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(File::open(path)?);
+        Ok(rdr.deserialize().map(|r| r.map_err(|e: csv::Error| e.to_string())).collect())
+    }
+}
+
+//This function writes the rows as CSV, or as a JSON array when the path ends in .json
+pub fn write_rows<T: Serialize>(path: &str, rows: &[T]) -> Result<(), Box<dyn Error>> {
+    if is_json(path) {
+        fs::write(path, serde_json::to_string_pretty(rows)?)?;
+    } else {
+        let mut writer = csv::Writer::from_path(path)?;
+        for row in rows {
+            writer.serialize(row)?;
+        }
+        writer.flush()?;
+    }
+    Ok(())
+}
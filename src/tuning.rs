@@ -0,0 +1,119 @@
+//This module learns the similarity weights from the historical days instead of using the hand-picked ones
+use crate::common::DaySummary;
+use crate::search::PredictConfig;
+use crate::similarity::{conditions_of, similarity_score, SimilarityConfig};
+
+//The values each weight is allowed to take during the search
+const WEIGHT_GRID: [f64; 8] = [0.0, 1.0, 2.5, 5.0, 10.0, 20.0, 30.0, 40.0];
+
+//This struct holds the settings for fitting: the prediction the weights are fitted for and how many passes over the weights are allowed
+//The nearest days are combined the way predict combines them, so the weights are fitted for the estimator they will be used with
+#[derive(Debug, Clone)]
+pub struct FitOptions {
+    pub predict: PredictConfig,
+    pub max_rounds: usize,
+}
+
+impl Default for FitOptions {
+    fn default() -> Self {
+        FitOptions { predict: PredictConfig::default(), max_rounds: 5 }
+    }
+}
+
+//This struct is the outcome of fitting with the mean absolute error before and after
+#[derive(Debug, Clone)]
+pub struct FitReport {
+    pub config: SimilarityConfig,
+    pub error_before: f64,
+    pub error_after: f64,
+    pub rounds: usize,
+}
+
+//This function predicts every day from the k most similar other days, weighted like predict weights them, and returns the mean absolute error
+//The day itself is left out, otherwise it would always be its own best match
+pub fn leave_one_out_error(days: &[DaySummary], config: &SimilarityConfig, predict: &PredictConfig) -> f64 {
+    let k = predict.k;
+    if days.len() < 2 || k == 0 {
+        return 0.0;
+    }
+    let mut total_error = 0.0;
+    for (i, day) in days.iter().enumerate() {
+        let query = conditions_of(day);
+        let mut scored: Vec<(f64, i32)> = days
+            .iter()
+            .enumerate()
+            .filter(|&(j, _)| j != i)
+            .map(|(_, other)| (similarity_score(other, &query, config), other.total_rentals))
+            .collect();
+        scored.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let nearest = &scored[..k.min(scored.len())];
+        let total_weight: f64 = nearest.iter().map(|&(score, _)| predict.weighting.weight(score)).sum();
+        let estimate = nearest.iter().map(|&(score, rentals)| predict.weighting.weight(score) * rentals as f64).sum::<f64>() / total_weight;
+        total_error += (estimate - day.total_rentals as f64).abs();
+    }
+    total_error / days.len() as f64
+}
+
+//This function fits the weights by coordinate descent: each weight in turn is set to the grid value with the lowest error,
+//and the passes stop once a whole pass makes no improvement or max_rounds is reached
+//The ranges of the numeric distances are kept from the starting config
+pub fn fit_weights(days: &[DaySummary], start: &SimilarityConfig, options: &FitOptions) -> FitReport {
+    let error_before = leave_one_out_error(days, start, &options.predict);
+    let mut best = start.clone();
+    let mut best_error = error_before;
+    let mut rounds = 0;
+
+    let names = ["month", "weather", "temperature", "precipitation", "temperature_c", "rainfall_mm"];
+    while rounds < options.max_rounds {
+        rounds += 1;
+        let mut improved = false;
+        for name in names {
+            for value in WEIGHT_GRID {
+                let mut candidate = best.clone();
+                //the grid only holds valid weights so set cannot fail here
+                candidate.set(name, value).unwrap();
+                let error = leave_one_out_error(days, &candidate, &options.predict);
+                if error < best_error {
+                    best_error = error;
+                    best = candidate;
+                    improved = true;
+                }
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+
+    FitReport {
+        config: best,
+        error_before,
+        error_after: best_error,
+        rounds,
+    }
+}
+
+//The test builds days whose rentals only follow the measured temperature and checks that fitting finds that
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{test_day, Weather};
+
+    #[test]
+    fn test_fit_weights_improves_error() {
+        let days: Vec<DaySummary> = (0..24)
+            .map(|i| DaySummary {
+                month: i % 12 + 1,
+                weather: if i % 2 == 0 { Weather::Sunny } else { Weather::Rainy },
+                avg_temperature: i as f64,
+                ..test_day(&format!("{:02}/{:02}/2018", i % 28 + 1, i % 12 + 1), 1000 + 500 * i as i32)
+            })
+            .collect();
+
+        let options = FitOptions { predict: PredictConfig { k: 1, ..PredictConfig::default() }, max_rounds: 3 };
+        let report = fit_weights(&days, &SimilarityConfig::default(), &options);
+        assert!(report.error_after < report.error_before);
+        assert!(report.config.temperature_c > 0.0);
+        assert_eq!(report.error_after, leave_one_out_error(&days, &report.config, &options.predict));
+    }
+}